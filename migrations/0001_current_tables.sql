@@ -0,0 +1,48 @@
+-- Tables for the Current module.  Earlier versions created these on every
+-- refresh, so replace whatever is there; a refresh reloads the data.
+DROP TABLE IF EXISTS curr_courses;
+DROP TABLE IF EXISTS curr_assignments;
+DROP TABLE IF EXISTS curr_students;
+DROP TABLE IF EXISTS curr_submissions;
+DROP TABLE IF EXISTS curr_sync;
+
+CREATE TABLE curr_courses(
+    id INT,
+    code TEXT,
+    concluded BOOLEAN,
+    term TEXT,
+    students INT
+);
+
+CREATE TABLE curr_assignments(
+    id INT,
+    course_id INT,
+    name TEXT,
+    points_possible INT,
+    assignment_group_id INT
+);
+
+CREATE TABLE curr_students(
+    id INT,
+    course_id INT,
+    name TEXT,
+    curr_grade TEXT,
+    curr_score REAL
+);
+
+CREATE TABLE curr_submissions(
+    id INT PRIMARY KEY,
+    assignment_id INT,
+    user_id INT,
+    score REAL,
+    excused BOOL,
+    missing BOOL,
+    late BOOL,
+    attempt INT,
+    current_submission BOOL
+);
+
+CREATE TABLE curr_sync(
+    course_id INT PRIMARY KEY,
+    submissions_synced TIMESTAMPTZ
+);
@@ -0,0 +1,29 @@
+-- Keys, foreign keys and indexes for the Current module tables.  Existing
+-- rows may contain duplicates, so they are cleared; the next refresh is a
+-- full refresh because curr_sync is emptied as well.
+DELETE FROM curr_submissions;
+DELETE FROM curr_students;
+DELETE FROM curr_assignments;
+DELETE FROM curr_courses;
+DELETE FROM curr_sync;
+
+ALTER TABLE curr_courses
+    ADD PRIMARY KEY (id);
+
+ALTER TABLE curr_assignments
+    ADD PRIMARY KEY (id),
+    ADD FOREIGN KEY (course_id) REFERENCES curr_courses(id) ON DELETE CASCADE;
+
+ALTER TABLE curr_students
+    ADD PRIMARY KEY (course_id, id),
+    ADD FOREIGN KEY (course_id) REFERENCES curr_courses(id) ON DELETE CASCADE;
+
+ALTER TABLE curr_submissions
+    ADD FOREIGN KEY (assignment_id) REFERENCES curr_assignments(id) ON DELETE CASCADE;
+
+ALTER TABLE curr_sync
+    ADD FOREIGN KEY (course_id) REFERENCES curr_courses(id) ON DELETE CASCADE;
+
+CREATE INDEX curr_assignments_course_idx ON curr_assignments(course_id);
+CREATE INDEX curr_submissions_assignment_idx ON curr_submissions(assignment_id);
+CREATE INDEX curr_submissions_user_idx ON curr_submissions(user_id);
@@ -0,0 +1,10 @@
+-- Points and scores are stored exactly; INT truncated fractional points and
+-- REAL rounded scores.
+ALTER TABLE curr_assignments
+    ALTER COLUMN points_possible TYPE NUMERIC;
+
+ALTER TABLE curr_students
+    ALTER COLUMN curr_score TYPE NUMERIC;
+
+ALTER TABLE curr_submissions
+    ALTER COLUMN score TYPE NUMERIC;
@@ -0,0 +1,17 @@
+-- Assignment groups with their weights and drop rules
+CREATE TABLE curr_assignment_groups(
+    id INT PRIMARY KEY,
+    course_id INT REFERENCES curr_courses(id) ON DELETE CASCADE,
+    name TEXT,
+    position INT,
+    group_weight NUMERIC,
+    drop_lowest INT,
+    drop_highest INT,
+    never_drop INT[]
+);
+
+CREATE INDEX curr_assignment_groups_course_idx ON curr_assignment_groups(course_id);
+
+-- Whether the course weights final grades by assignment group
+ALTER TABLE curr_courses
+    ADD COLUMN weighted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Assignments that Canvas leaves out of the final grade
+ALTER TABLE curr_assignments
+    ADD COLUMN omit_from_final_grade BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+-- Letter grade cutoffs from each course's Canvas grading standard
+CREATE TABLE curr_grading_schemes(
+    course_id INT REFERENCES curr_courses(id) ON DELETE CASCADE,
+    name TEXT,
+    min_score NUMERIC,
+    PRIMARY KEY (course_id, name)
+);
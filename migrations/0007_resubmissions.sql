@@ -0,0 +1,13 @@
+-- Every attempt of every submission, from the Canvas submission history
+CREATE TABLE resub_attempts(
+    submission_id INT,
+    attempt INT,
+    course_id INT,
+    assignment_id INT,
+    user_id INT,
+    score NUMERIC,
+    submitted_at TIMESTAMPTZ,
+    PRIMARY KEY (submission_id, attempt)
+);
+
+CREATE INDEX resub_attempts_course_idx ON resub_attempts(course_id);
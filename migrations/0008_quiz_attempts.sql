@@ -0,0 +1,19 @@
+-- Quiz behind an assignment, if any
+ALTER TABLE curr_assignments
+    ADD COLUMN quiz_id INT;
+
+-- Every completed attempt of every quiz, from the submission history of
+-- the quiz's assignment
+CREATE TABLE quiz_attempts(
+    quiz_id INT,
+    user_id INT,
+    attempt INT,
+    course_id INT,
+    assignment_id INT,
+    score NUMERIC,
+    points_possible NUMERIC,
+    finished_at TIMESTAMPTZ,
+    PRIMARY KEY (quiz_id, user_id, attempt)
+);
+
+CREATE INDEX quiz_attempts_course_idx ON quiz_attempts(course_id);
@@ -0,0 +1,10 @@
+-- Attendance imported from Roll Call CSV exports, one row per student and
+-- class meeting
+CREATE TABLE att_records(
+    course_id INT,
+    user_id INT,
+    name TEXT,
+    class_date DATE,
+    status TEXT,
+    PRIMARY KEY (course_id, user_id, class_date)
+);
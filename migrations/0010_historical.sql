@@ -0,0 +1,42 @@
+-- Concluded courses archived by the Historical module.  Unlike the curr_*
+-- tables these are never cleared by a Current refresh, so past terms stay
+-- available for comparison.
+CREATE TABLE hist_courses(
+    id INT PRIMARY KEY,
+    code TEXT NOT NULL,
+    term TEXT NOT NULL,
+    students INT,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX hist_courses_code_idx ON hist_courses(code, term);
+
+CREATE TABLE hist_students(
+    course_id INT REFERENCES hist_courses(id) ON DELETE CASCADE,
+    id INT,
+    name TEXT,
+    final_grade TEXT,
+    final_score NUMERIC,
+    PRIMARY KEY (course_id, id)
+);
+
+CREATE TABLE hist_assignments(
+    course_id INT REFERENCES hist_courses(id) ON DELETE CASCADE,
+    id INT,
+    name TEXT,
+    group_name TEXT,
+    points_possible NUMERIC,
+    PRIMARY KEY (course_id, id)
+);
+
+CREATE TABLE hist_submissions(
+    course_id INT,
+    assignment_id INT,
+    user_id INT,
+    score NUMERIC,
+    excused BOOLEAN,
+    missing BOOLEAN,
+    PRIMARY KEY (course_id, assignment_id, user_id),
+    FOREIGN KEY (course_id, assignment_id)
+        REFERENCES hist_assignments(course_id, id) ON DELETE CASCADE
+);
@@ -0,0 +1,48 @@
+-- Learning outcomes linked into each course, with the rule Canvas uses to
+-- roll a student's results up into one score
+CREATE TABLE out_outcomes(
+    course_id INT,
+    id INT,
+    title TEXT,
+    description TEXT,
+    mastery_points NUMERIC,
+    points_possible NUMERIC,
+    calculation_method TEXT,
+    calculation_int INT,
+    PRIMARY KEY (course_id, id)
+);
+
+-- Rubric criteria that assess an outcome, and the assignments using the rubric
+CREATE TABLE out_rubric_links(
+    course_id INT,
+    rubric_id INT,
+    criterion_id TEXT,
+    outcome_id INT,
+    rubric_title TEXT,
+    assignment_ids INT[],
+    PRIMARY KEY (course_id, rubric_id, criterion_id)
+);
+
+-- Every assessment of an outcome, from the outcome_results API
+CREATE TABLE out_results(
+    id INT PRIMARY KEY,
+    course_id INT,
+    outcome_id INT,
+    user_id INT,
+    alignment TEXT,
+    score NUMERIC,
+    possible NUMERIC,
+    mastery BOOLEAN,
+    assessed_at TIMESTAMPTZ
+);
+
+CREATE INDEX out_results_course_idx ON out_results(course_id, outcome_id);
+
+-- Section membership of the students in each course
+CREATE TABLE curr_sections(
+    course_id INT,
+    id INT,
+    name TEXT,
+    user_id INT,
+    PRIMARY KEY (course_id, id, user_id)
+);
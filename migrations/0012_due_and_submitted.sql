@@ -0,0 +1,10 @@
+-- When work was due and when it was turned in, for the at-risk report
+ALTER TABLE curr_assignments
+    ADD COLUMN due_at TIMESTAMPTZ;
+
+ALTER TABLE curr_submissions
+    ADD COLUMN submitted_at TIMESTAMPTZ;
+
+-- Submissions synced before this have no submitted_at, so the next refresh
+-- is a full refresh
+DELETE FROM curr_sync;
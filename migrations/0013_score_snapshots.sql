@@ -0,0 +1,10 @@
+-- Each enrollment's current score as of every Current refresh.  Refreshes
+-- overwrite curr_students, so this is never cleared and keeps the history.
+CREATE TABLE score_snapshots(
+    course_id INT,
+    user_id INT,
+    taken_at TIMESTAMPTZ,
+    curr_grade TEXT,
+    curr_score NUMERIC,
+    PRIMARY KEY (course_id, user_id, taken_at)
+);
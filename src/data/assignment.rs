@@ -1,69 +1,91 @@
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use sqlx::{Pool, Postgres};
-use crate::data::connections::json_api_get;
-use crate::data::config::Config;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use sqlx::{Postgres, Transaction};
+use crate::data::connections::CanvasClient;
 use crate::macros::err;
 
 #[derive(Deserialize)]
 pub struct Assignment {
     pub id : i32,
+    #[serde(default)]
+    pub course_id : i32,
     pub name : String,
-    pub quiz_id : Option<u32>,
-    pub points_possible : f32,
-    pub assignment_group_id : i32
+    pub quiz_id : Option<i32>,
+    pub points_possible : Decimal,
+    pub assignment_group_id : i32,
+    #[serde(default)]
+    pub omit_from_final_grade : bool,
+    pub due_at : Option<DateTime<Utc>>,
 }
 
 impl Assignment {
-    pub async fn load(database : &Pool<Postgres>, config : &Config, course_id : i32) -> Result<(), String> {
-        let assignments = json_api_get::<Assignment>(config,&format!(
-                            "/api/v1/courses/{}/assignments?", course_id))
-                            .await?;
-        for a in assignments.iter() {
-            sqlx::query(
-                "
-                INSERT INTO curr_assignments 
-                (id, course_id, name, points_possible,
-                 assignment_group_id)
-                    VALUES
-                ($1, $2, $3, $4, $5);
-            ")
-            .bind(a.id)
-            .bind(course_id)
-            .bind(a.name.clone())
-            .bind(a.points_possible)
-            .bind(a.assignment_group_id)
-            .execute(database)
-            .await
-            .map_err(|e| err!("Assignment SQL Failure", e))?;
-        }
-        Ok(())
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let mut assignments = client.json_api_get::<Assignment>(&format!(
+            "/api/v1/courses/{}/assignments?", course_id))
+            .await?;
+        assignments.iter_mut().for_each(|x| x.course_id = course_id);
+        Ok(assignments)
     }
 
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, assignments : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, assignments).await
+    }
 
-    pub async fn create_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query(
-         "
-             CREATE TABLE curr_assignments(
-                 id INT,
-                 course_id INT,
-                 name TEXT,
-                 points_possible INT,
-                 assignment_group_id INT
-             );
-         ")
-         .execute(database)
-         .await
-         .map_err(|e| err!("SQL Assignment Table Creation Failure",e))?;
-         Ok(())
-     }
 
-     pub async fn drop_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query("DROP TABLE IF EXISTS curr_assignments;")
-            .execute(database)
-            .await
-            .map_err(|e| err!("SQL Assignment Table Drop Failure",e))?;
+    /// Removes assignments of the refreshed courses that Canvas no longer
+    /// returns.  Their submissions go with them through the foreign key.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], assignments : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM curr_assignments
+            WHERE course_id = ANY($1) AND NOT (id = ANY($2));
+        ")
+        .bind(course_ids)
+        .bind(assignments.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Assignment Table Cleanup Failure",e))?;
         Ok(())
     }
 
 
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl BulkInsert for Assignment {
+    const TABLE : &'static str = "curr_assignments";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_assignments 
+            (id, course_id, name, points_possible,
+             assignment_group_id, omit_from_final_grade, quiz_id, due_at)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::NUMERIC[], $5::INT[],
+                                 $6::BOOLEAN[], $7::INT[], $8::TIMESTAMPTZ[])
+            ON CONFLICT (id) DO UPDATE SET
+                course_id = EXCLUDED.course_id,
+                name = EXCLUDED.name,
+                points_possible = EXCLUDED.points_possible,
+                assignment_group_id = EXCLUDED.assignment_group_id,
+                omit_from_final_grade = EXCLUDED.omit_from_final_grade,
+                quiz_id = EXCLUDED.quiz_id,
+                due_at = EXCLUDED.due_at;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.points_possible).collect::<Vec<Decimal>>())
+        .bind(rows.iter().map(|x| x.assignment_group_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.omit_from_final_grade).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.quiz_id).collect::<Vec<Option<i32>>>())
+        .bind(rows.iter().map(|x| x.due_at).collect::<Vec<Option<DateTime<Utc>>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Assignment SQL Failure", e))?;
+        Ok(())
+    }
+}
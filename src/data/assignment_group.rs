@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{array_literals, bulk_insert, BulkInsert, BulkStats};
+use sqlx::{Postgres, Transaction};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+#[derive(Deserialize)]
+pub struct AssignmentGroup {
+    pub id : i32,
+    #[serde(default)]
+    pub course_id : i32,
+    pub name : String,
+    pub position : Option<i32>,
+    pub group_weight : Option<Decimal>,
+    pub rules : Option<GroupRules>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct GroupRules {
+    pub drop_lowest : Option<i32>,
+    pub drop_highest : Option<i32>,
+    pub never_drop : Option<Vec<i32>>,
+}
+
+impl AssignmentGroup {
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let mut groups = client.json_api_get::<AssignmentGroup>(&format!(
+            "/api/v1/courses/{}/assignment_groups?", course_id))
+            .await?;
+        groups.iter_mut().for_each(|x| x.course_id = course_id);
+        Ok(groups)
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, groups : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, groups).await
+    }
+
+    /// Removes groups of the refreshed courses that Canvas no longer returns.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], groups : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM curr_assignment_groups
+            WHERE course_id = ANY($1) AND NOT (id = ANY($2));
+        ")
+        .bind(course_ids)
+        .bind(groups.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Assignment Group Table Cleanup Failure",e))?;
+        Ok(())
+    }
+
+    fn rules(&self) -> (i32, i32, Vec<i32>) {
+        let rules = self.rules.as_ref();
+        (
+            rules.and_then(|x| x.drop_lowest).unwrap_or(0),
+            rules.and_then(|x| x.drop_highest).unwrap_or(0),
+            rules.and_then(|x| x.never_drop.clone()).unwrap_or_default()
+        )
+    }
+}
+
+#[async_trait]
+impl BulkInsert for AssignmentGroup {
+    const TABLE : &'static str = "curr_assignment_groups";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        let rules = rows.iter()
+            .map(|x| x.rules())
+            .collect::<Vec<(i32, i32, Vec<i32>)>>();
+        let never_drop = array_literals(&rules.iter()
+            .map(|x| x.2.clone())
+            .collect::<Vec<Vec<i32>>>());
+        sqlx::query(
+            "
+            INSERT INTO curr_assignment_groups
+            (id, course_id, name, position, group_weight,
+             drop_lowest, drop_highest, never_drop)
+            SELECT id, course_id, name, position, group_weight,
+                   drop_lowest, drop_highest, never_drop::INT[]
+            FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::INT[], $5::NUMERIC[],
+                        $6::INT[], $7::INT[], $8::TEXT[])
+                AS g(id, course_id, name, position, group_weight,
+                     drop_lowest, drop_highest, never_drop)
+            ON CONFLICT (id) DO UPDATE SET
+                course_id = EXCLUDED.course_id,
+                name = EXCLUDED.name,
+                position = EXCLUDED.position,
+                group_weight = EXCLUDED.group_weight,
+                drop_lowest = EXCLUDED.drop_lowest,
+                drop_highest = EXCLUDED.drop_highest,
+                never_drop = EXCLUDED.never_drop;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.position.unwrap_or(0)).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.group_weight.unwrap_or_default()).collect::<Vec<Decimal>>())
+        .bind(rules.iter().map(|x| x.0).collect::<Vec<i32>>())
+        .bind(rules.iter().map(|x| x.1).collect::<Vec<i32>>())
+        .bind(never_drop)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Assignment Group SQL Failure", e))?;
+        Ok(())
+    }
+}
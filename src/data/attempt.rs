@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use serde::Deserialize;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+#[derive(Deserialize)]
+struct HistorySubmission {
+    id : i32,
+    assignment_id : i32,
+    user_id : i32,
+    #[serde(default)]
+    submission_history : Vec<SubmissionVersion>,
+}
+
+// Canvas saves a version whenever a submission is submitted or graded
+#[derive(Deserialize)]
+struct SubmissionVersion {
+    attempt : Option<i32>,
+    score : Option<Decimal>,
+    submitted_at : Option<DateTime<Utc>>,
+    grade_matches_current_submission : Option<bool>,
+}
+
+/// One attempt at a submission with the score it was given, if it was
+/// graded before the next attempt.
+pub struct Attempt {
+    pub submission_id : i32,
+    pub attempt : i32,
+    pub course_id : i32,
+    pub assignment_id : i32,
+    pub user_id : i32,
+    pub score : Option<Decimal>,
+    pub submitted_at : Option<DateTime<Utc>>,
+}
+
+impl Attempt {
+    pub async fn fetch(client : &CanvasClient, course : i32) -> Result<Vec<Self>, String> {
+        let submissions = client.json_api_get::<HistorySubmission>(&format!(
+            "/api/v1/courses/{}/students/submissions\
+            ?student_ids[]=all\
+            &enrollment_state=active\
+            &include[]=submission_history", course))
+            .await?;
+        Ok(Attempt::from_history(course, submissions))
+    }
+
+    /// Downloads the attempts at one assignment.
+    pub async fn fetch_assignment(client : &CanvasClient, course : i32, assignment : i32) -> Result<Vec<Self>, String> {
+        let submissions = client.json_api_get::<HistorySubmission>(&format!(
+            "/api/v1/courses/{}/assignments/{}/submissions\
+            ?include[]=submission_history", course, assignment))
+            .await?;
+        Ok(Attempt::from_history(course, submissions))
+    }
+
+    fn from_history(course : i32, submissions : Vec<HistorySubmission>) -> Vec<Self> {
+        let mut attempts = Vec::<Attempt>::new();
+        for submission in submissions {
+            // Keep the last version of each attempt.  A version whose grade
+            // belongs to an earlier attempt has not been graded yet.
+            let mut versions = BTreeMap::<i32, SubmissionVersion>::new();
+            for version in submission.submission_history {
+                if let Some(attempt) = version.attempt.filter(|x| *x > 0) {
+                    versions.insert(attempt, version);
+                }
+            }
+            attempts.extend(versions.into_iter().map(|(attempt, version)| Attempt {
+                submission_id : submission.id,
+                attempt,
+                course_id : course,
+                assignment_id : submission.assignment_id,
+                user_id : submission.user_id,
+                score : version.score.filter(|_| version.grade_matches_current_submission.unwrap_or(true)),
+                submitted_at : version.submitted_at,
+            }));
+        }
+        attempts
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, attempts : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, attempts).await
+    }
+
+    /// Removes attempts that are no longer part of the refreshed courses, and
+    /// everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], attempts : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM resub_attempts AS att
+            WHERE NOT (att.course_id = ANY($1)) OR NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[]) AS keep(submission_id, attempt)
+                WHERE keep.submission_id = att.submission_id AND keep.attempt = att.attempt);
+        ")
+        .bind(course_ids)
+        .bind(attempts.iter().map(|x| x.submission_id).collect::<Vec<i32>>())
+        .bind(attempts.iter().map(|x| x.attempt).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Attempt Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for Attempt {
+    const TABLE : &'static str = "resub_attempts";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO resub_attempts
+            (submission_id, attempt, course_id, assignment_id, user_id, score, submitted_at)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::INT[], $4::INT[], $5::INT[],
+                                 $6::NUMERIC[], $7::TIMESTAMPTZ[])
+            ON CONFLICT (submission_id, attempt) DO UPDATE SET
+                course_id = EXCLUDED.course_id,
+                assignment_id = EXCLUDED.assignment_id,
+                user_id = EXCLUDED.user_id,
+                score = EXCLUDED.score,
+                submitted_at = EXCLUDED.submitted_at;
+        ")
+        .bind(rows.iter().map(|x| x.submission_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.attempt).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.assignment_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.score).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.submitted_at).collect::<Vec<Option<DateTime<Utc>>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Attempt SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, unique_rows, BulkInsert, BulkStats};
+use crate::macros::err;
+
+pub const STATUSES : [&str; 4] = ["present", "late", "absent", "excused"];
+
+/// One student's attendance at one class meeting.
+pub struct AttendanceRecord {
+    pub course_id : i32,
+    pub user_id : i32,
+    pub name : String,
+    pub class_date : NaiveDate,
+    pub status : String,
+}
+
+impl AttendanceRecord {
+    /// Reads a Roll Call attendance export.  Columns are found by their
+    /// header, Student Name and Course ID are optional, and rows for other
+    /// courses are skipped when the export has a Course ID column.
+    pub fn from_csv(path : &Path, course_id : i32) -> Result<Vec<Self>, String> {
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|e| err!(format!("CSV File Failure\n{}", path.display()), e))?;
+        let headers = reader.headers()
+            .map_err(|e| err!(format!("CSV Parsing Failure\n{}", path.display()), e))?
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.trim().to_lowercase(), i))
+            .collect::<HashMap<String, usize>>();
+        let column = |name : &str| headers.get(name).copied()
+            .ok_or(err!(format!("CSV Parsing Failure\n{}", path.display()),
+                format!("Missing column: {}", name)));
+        let user_column = column("student id")?;
+        let name_column = headers.get("student name").copied();
+        let date_column = column("class date")?;
+        let status_column = column("attendance")?;
+        let course_column = headers.get("course id").copied();
+
+        let mut records = Vec::<AttendanceRecord>::new();
+        for (line, row) in reader.records().enumerate() {
+            let row = row.map_err(|e| err!(format!("CSV Parsing Failure\n{}", path.display()), e))?;
+            let field = |i : usize| row.get(i).unwrap_or("").trim();
+            let location = || format!("{} line {}", path.display(), line + 2);
+            if course_column.is_some_and(|i| field(i) != course_id.to_string()) {
+                continue;
+            }
+            let user_id = field(user_column).parse::<i32>()
+                .map_err(|e| err!(format!("CSV Parsing Failure\n{}", location()), e))?;
+            let class_date = NaiveDate::parse_from_str(field(date_column), "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(field(date_column), "%m/%d/%Y"))
+                .map_err(|e| err!(format!("CSV Parsing Failure\n{}", location()), e))?;
+            let status = field(status_column).to_lowercase();
+            if !STATUSES.contains(&status.as_str()) {
+                return Err(err!(format!("CSV Parsing Failure\n{}", location()),
+                    format!("Unknown attendance: {}", status)));
+            }
+            records.push(AttendanceRecord {
+                course_id,
+                user_id,
+                name : name_column.map(|i| field(i).to_string()).unwrap_or_default(),
+                class_date,
+                status,
+            });
+        }
+        Ok(unique_rows(records, |x| (x.user_id, x.class_date)))
+    }
+
+    /// Replaces everything imported for the course with `records`.
+    pub async fn replace(tx : &mut Transaction<'_, Postgres>, course_id : i32, records : &[Self]) -> Result<BulkStats, String> {
+        sqlx::query("DELETE FROM att_records WHERE course_id = $1;")
+            .bind(course_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| err!("SQL Attendance Table Cleanup Failure",e))?;
+        bulk_insert(tx, records).await
+    }
+}
+
+#[async_trait]
+impl BulkInsert for AttendanceRecord {
+    const TABLE : &'static str = "att_records";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO att_records
+            (course_id, user_id, name, class_date, status)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::DATE[], $5::TEXT[]);
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.class_date).collect::<Vec<NaiveDate>>())
+        .bind(rows.iter().map(|x| x.status.clone()).collect::<Vec<String>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Attendance SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use sqlx::{Postgres, Transaction};
+
+// Rows per INSERT statement.  Each column is sent as one array parameter, so
+// this only bounds the statement size, not the number of bind parameters.
+const CHUNK_SIZE : usize = 2000;
+
+/// A row type that can be written many rows at a time, typically with a
+/// single `INSERT ... SELECT * FROM UNNEST(...)` per chunk.
+#[async_trait]
+pub trait BulkInsert : Sized + Sync {
+    const TABLE : &'static str;
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String>;
+}
+
+/// How long a bulk insert took, reported after each refresh.
+pub struct BulkStats {
+    pub table : &'static str,
+    pub rows : usize,
+    pub elapsed : Duration,
+}
+
+impl fmt::Display for BulkStats {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let rate = if secs > 0.0 { self.rows as f64 / secs } else { 0.0 };
+        write!(f, "{:<20} {:>8} rows {:>8.2}s {:>10.0} rows/sec",
+            self.table, self.rows, secs, rate)
+    }
+}
+
+pub async fn bulk_insert<T>(tx : &mut Transaction<'_, Postgres>, rows : &[T]) -> Result<BulkStats, String>
+    where T : BulkInsert
+{
+    let start = Instant::now();
+    for chunk in rows.chunks(CHUNK_SIZE) {
+        T::insert_chunk(tx, chunk).await?;
+    }
+    Ok(BulkStats { table : T::TABLE, rows : rows.len(), elapsed : start.elapsed() })
+}
+
+/// Drops rows with a repeated key, keeping the last one.  An upsert cannot
+/// touch the same row twice in one statement, and a row can show up on two
+/// pages when Canvas data changes while it is being paged through.
+pub fn unique_rows<T, K>(rows : Vec<T>, key : impl Fn(&T) -> K) -> Vec<T>
+    where K : Hash + Eq
+{
+    let mut seen = HashSet::<K>::new();
+    let mut unique = rows.into_iter()
+        .rev()
+        .filter(|x| seen.insert(key(x)))
+        .collect::<Vec<T>>();
+    unique.reverse();
+    unique
+}
+
+/// Encodes each list as a Postgres array literal such as `{1,2,3}`.  UNNEST
+/// flattens nested arrays, so an array column is bound as TEXT[] and cast
+/// back per row.
+pub fn array_literals(lists : &[Vec<i32>]) -> Vec<String> {
+    lists.iter()
+        .map(|x| format!("{{{}}}", x.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(",")))
+        .collect()
+}
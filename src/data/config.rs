@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use serde::Deserialize;
+use rust_decimal::Decimal;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use crate::macros::err;
@@ -7,6 +9,10 @@ use crate::macros::err;
 pub struct Config {
     pub general : GeneralConfig,
     pub current_config : CurrentConfig,
+    #[serde(default)]
+    pub atrisk_config : AtRiskConfig,
+    #[serde(default)]
+    pub attendance_config : AttendanceConfig,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -14,13 +20,84 @@ pub struct GeneralConfig {
     pub server : String,
     pub token : String,
     pub postgres : String,
-    pub postgres_pool : i32
+    pub postgres_pool : i32,
+    pub api_concurrency : Option<usize>,
+    pub api_mode : Option<String>,
+    pub fixtures : Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct CurrentConfig {
     pub exclude_zero_grades : bool,
     pub courses : Vec<(String, i32)>,
+    // Per course letter cutoffs (percent) replacing the Canvas grading standard
+    pub grading_schemes : Option<HashMap<String, Vec<(String, Decimal)>>>,
+}
+
+/// Thresholds for the risk signals of the `atrisk` command, each adding its
+/// weight to a student's risk when it fires.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AtRiskConfig {
+    // Current score (percent) below which a student is flagged
+    pub score_threshold : Decimal,
+    pub score_weight : Decimal,
+    // Missing assignments needed to flag a student
+    pub missing_threshold : usize,
+    pub missing_weight : Decimal,
+    // Zeros on assignments due in the last recent_days days
+    pub recent_days : i64,
+    pub recent_zero_weight : Decimal,
+    // Average of the last trend_window graded assignments dropping by at
+    // least trend_drop points against the earlier ones
+    pub trend_window : usize,
+    pub trend_drop : Decimal,
+    pub trend_weight : Decimal,
+    // Nothing submitted in the last inactive_days days
+    pub inactive_days : i64,
+    pub inactive_weight : Decimal,
+}
+
+impl Default for AtRiskConfig {
+    fn default() -> Self {
+        Self {
+            score_threshold : Decimal::from(70),
+            score_weight : Decimal::from(3),
+            missing_threshold : 2,
+            missing_weight : Decimal::from(2),
+            recent_days : 14,
+            recent_zero_weight : Decimal::from(2),
+            trend_window : 3,
+            trend_drop : Decimal::from(10),
+            trend_weight : Decimal::ONE,
+            inactive_days : 10,
+            inactive_weight : Decimal::from(2),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AttendanceConfig {
+    // Canvas assignment Roll Call reports attendance to
+    pub assignment : String,
+    // Credit for arriving late, Roll Call counts a late arrival as 80%
+    pub late_credit : Decimal,
+    // Attendance percentage where the bonus starts, growing linearly to
+    // bonus_max at 100% attendance
+    pub bonus_threshold : Decimal,
+    pub bonus_max : Decimal,
+}
+
+impl Default for AttendanceConfig {
+    fn default() -> Self {
+        Self {
+            assignment : "Roll Call Attendance".to_string(),
+            late_credit : Decimal::new(8, 1),
+            bonus_threshold : Decimal::from(80),
+            bonus_max : Decimal::from(2),
+        }
+    }
 }
 
 impl Config {
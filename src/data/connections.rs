@@ -1,83 +1,476 @@
+use std::fmt;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use serde::de::DeserializeOwned;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
-use regex::Regex;
+use reqwest::{Response, StatusCode, Url};
+use tokio::sync::Semaphore;
 use crate::data::config::Config;
+use crate::data::fixtures::{ApiMode, Fixture};
+use crate::data::links::PageLinks;
 use crate::macros::err;
 
-pub async fn json_api_get<T>(config : &Config, rel_url : &str) -> Result<Vec<T>, String> 
-    where T : DeserializeOwned
-{
-    // println!("======================\n{}",rel_url);
-    let client = reqwest::Client::new();
-    let mut all_results = Vec::<T>::new();
-    let mut url = format!("{}/{}&per_page=100",config.general.server, rel_url);
-    loop {
-
-        // println!("{}",url);
-        let res = client.get(url.clone())
-            .header("Authorization", format!("Bearer {}", config.general.token))
-            .send()
-            .await
-            .map_err(|e| err!(format!("API Request Failure\n{}",url),e))?;
-        
-        let headers = res.headers().clone();
+const PER_PAGE : u32 = 100;
+const DEFAULT_CONCURRENCY : usize = 4;
+const MAX_RETRIES : u32 = 5;
+// Tests run against a local server, so keep their waits short
+#[cfg(not(test))]
+const BACKOFF_BASE_MS : u64 = 500;
+#[cfg(not(test))]
+const BACKOFF_MAX_MS : u64 = 16_000;
+#[cfg(test)]
+const BACKOFF_BASE_MS : u64 = 1;
+#[cfg(test)]
+const BACKOFF_MAX_MS : u64 = 400;
+// Canvas starts each token with a bucket of 700 units.  Once the remaining
+// quota drops below this we slow down instead of waiting to be throttled.
+const RATE_LIMIT_LOW_WATER : f64 = 200.0;
+
+/// Shared Canvas API client.  Cloning is cheap: every clone uses the same
+/// connection pool, concurrency limit, rate limit state and statistics.
+#[derive(Clone)]
+pub struct CanvasClient {
+    client : reqwest::Client,
+    server : String,
+    token : String,
+    mode : ApiMode,
+    limiter : Arc<Semaphore>,
+    rate_remaining : Arc<Mutex<Option<f64>>>,
+    stats : Arc<ApiCounters>,
+}
+
+#[derive(Default)]
+struct ApiCounters {
+    requests : AtomicU64,
+    retries : AtomicU64,
+    throttled : AtomicU64,
+    server_errors : AtomicU64,
+    // Stored as thousandths so the cost can live in an atomic
+    cost_milli : AtomicU64,
+}
+
+struct Page {
+    body : String,
+    link : Option<String>,
+}
+
+/// Snapshot of the API traffic since the last call to `reset_stats`.
+pub struct ApiStats {
+    pub requests : u64,
+    pub retries : u64,
+    pub throttled : u64,
+    pub server_errors : u64,
+    pub cost : f64,
+    pub rate_remaining : Option<f64>,
+}
+
+impl fmt::Display for ApiStats {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "API Requests: {} (Retries: {}, Throttled: {}, Server Errors: {}, Cost: {:.2}",
+            self.requests, self.retries, self.throttled, self.server_errors, self.cost)?;
+        if let Some(remaining) = self.rate_remaining {
+            write!(f, ", Rate Limit Remaining: {:.2}", remaining)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl CanvasClient {
+
+    pub fn new(config : &Config, mode : ApiMode) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .map_err(|e| err!("API Client Creation Failure",e))?;
+        let concurrency = config.general.api_concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        Ok(Self {
+            client,
+            server : config.general.server.clone(),
+            token : config.general.token.clone(),
+            mode,
+            limiter : Arc::new(Semaphore::new(concurrency)),
+            rate_remaining : Arc::new(Mutex::new(None)),
+            stats : Arc::new(ApiCounters::default()),
+        })
+    }
+
+    pub async fn json_api_get<T>(&self, rel_url : &str) -> Result<Vec<T>, String>
+        where T : DeserializeOwned
+    {
+        self.json_api_get_pages(rel_url, |body| serde_json::from_str::<Vec<T>>(body)).await
+    }
+
+    /// Like `json_api_get` for endpoints that wrap each page's list in an
+    /// object, such as `{"outcome_results" : [...]}`.
+    pub async fn json_api_get_wrapped<T>(&self, rel_url : &str, key : &str) -> Result<Vec<T>, String>
+        where T : DeserializeOwned
+    {
+        self.json_api_get_pages(rel_url, |body| {
+            let mut page = serde_json::from_str::<serde_json::Value>(body)?;
+            serde_json::from_value::<Vec<T>>(page[key].take())
+        }).await
+    }
+
+    async fn json_api_get_pages<T>(&self, rel_url : &str,
+                                   parse : impl Fn(&str) -> Result<Vec<T>, serde_json::Error>) -> Result<Vec<T>, String>
+    {
+        let mut all_results = Vec::<T>::new();
+        let mut url = self.api_url(rel_url, Some(PER_PAGE))?;
+        let mut visited = HashSet::<String>::new();
+        loop {
+            visited.insert(url.to_string());
+            let page = self.fetch(&url).await?;
+
+            let results = parse(&page.body)
+            .map_err(|e| err!(format!("API JSON Conversion Failure\n{}",url),e))?;
+            all_results.extend(results);
+
+            let Some(header) = page.link else {
+                break;
+            };
+            let links = PageLinks::parse(&header, &url)?;
+            let Some(next) = links.next else {
+                break;
+            };
+            if visited.contains(&next) {
+                return Err(err!(format!("API Pagination Failure\n{}",url),
+                    format!("Next page points back to an earlier page: {}",next)));
+            }
+            url = Url::parse(&next)
+                .map_err(|e| err!(format!("API Pagination Failure\n{}",url),e))?;
+        }
+        Ok(all_results)
+    }
+
+    pub async fn json_api_get_single<T>(&self, rel_url : &str) -> Result<T, String>
+        where T : DeserializeOwned
+    {
+        let url = self.api_url(rel_url, None)?;
+        let page = self.fetch(&url).await?;
+
+        let result: T = serde_json::from_str(&page.body)
+        .map_err(|e| err!(format!("API JSON Conversion Failure\n{}",url),e))?;
+        Ok(result)
+    }
+
+    pub fn mode(&self) -> &ApiMode {
+        &self.mode
+    }
+
+    /// Retrieves one page according to the API mode, returning its body and
+    /// Link header.
+    async fn fetch(&self, url : &Url) -> Result<Page, String> {
+        if let ApiMode::Replay(dir) = &self.mode {
+            self.stats.requests.fetch_add(1, Ordering::Relaxed);
+            let fixture = Fixture::load(dir, url)?;
+            let body = serde_json::to_string(&fixture.body)
+                .map_err(|e| err!(format!("Fixture Conversion Failure\n{}",url),e))?;
+            return Ok(Page { body, link : fixture.link });
+        }
+
+        let res = self.send(url.as_str()).await?;
+        let link = match res.headers().get("link") {
+            Some(value) => Some(value.to_str()
+                .map_err(|e| err!(format!("API Header Parsing Failure\n{}",url),e))?
+                .to_string()),
+            None => None
+        };
         // Note that text() consumes the response
-        let data = res.text()
+        let body = res.text()
             .await
             .map_err(|e| err!(format!("API Response Parsing Failure\n{}",url),e))?;
-        // println!("{}", data);
-        // println!("{:?}", headers);
-        let results: Vec<T> = serde_json::from_str(&data)
-        .map_err(|e| err!(format!("API JSON Conversion Failure\n{}",url),e))?;
-        all_results.extend(results);
-
-        match headers.get("link") {
-            Some(value) => {
-                let links = value.to_str()
-                    .map_err(|e| err!(format!("API Header Parsing Failure\n{}",url),e))?;
-                // println!("Links: {}", links);
-                let re = Regex::new("<([^<]+)>; rel=\"next\",").unwrap();
-                let Some(next) = re.captures(links) else {
-                    // println!("No match.");
-                    break;
-                };
-                // println!("Match: {}", &next[1]);
-                url = next[1].to_string();
-            },
-            None => break
+
+        if let ApiMode::Record(dir) = &self.mode {
+            let fixture = Fixture {
+                url : Fixture::key(url),
+                link : link.clone(),
+                body : serde_json::from_str(&body)
+                    .map_err(|e| err!(format!("API JSON Conversion Failure\n{}",url),e))?,
+            };
+            fixture.save(dir, url)?;
         }
+        Ok(Page { body, link })
+    }
 
+    /// Joins a path such as `/api/v1/courses/1/users?include[]=x` onto the
+    /// server, adding `per_page` unless the caller already set one.
+    fn api_url(&self, rel_url : &str, per_page : Option<u32>) -> Result<Url, String> {
+        let full = format!("{}/{}",
+            self.server.trim_end_matches('/'),
+            rel_url.trim_start_matches('/'));
+        let mut url = Url::parse(&full)
+            .map_err(|e| err!(format!("API URL Failure\n{}",full),e))?;
+        if let Some(per_page) = per_page {
+            if !url.query_pairs().any(|(k, _)| k == "per_page") {
+                url.query_pairs_mut().append_pair("per_page", &per_page.to_string());
+            }
+        }
+        Ok(url)
     }
-    Ok(all_results)
-}
 
-pub async fn json_api_get_single<T>(config : &Config, rel_url : &str) -> Result<T, String> 
-    where T : DeserializeOwned
-{
-    // println!("======================\n{}",rel_url);
-    let client = reqwest::Client::new();
-    let url = format!("{}/{}&per_page=200",config.general.server, rel_url);
-    let res = client.get(url.clone())
-        .header("Authorization", format!("Bearer {}", config.general.token))
-        .send()
-        .await
-        .map_err(|e| err!(format!("API Request Failure\n{}",url),e))?;
-        
-    let data = res.text()
-        .await
-        .map_err(|e| err!(format!("API Response Parsing Failure\n{}",url),e))?;
-    // println!("{}", data);
-    let result: T = serde_json::from_str(&data)
-    .map_err(|e| err!(format!("API JSON Conversion Failure\n{}",url),e))?;
-    Ok(result)
+    pub fn stats(&self) -> ApiStats {
+        ApiStats {
+            requests : self.stats.requests.load(Ordering::Relaxed),
+            retries : self.stats.retries.load(Ordering::Relaxed),
+            throttled : self.stats.throttled.load(Ordering::Relaxed),
+            server_errors : self.stats.server_errors.load(Ordering::Relaxed),
+            cost : self.stats.cost_milli.load(Ordering::Relaxed) as f64 / 1000.0,
+            rate_remaining : *self.rate_remaining.lock().unwrap(),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.requests.store(0, Ordering::Relaxed);
+        self.stats.retries.store(0, Ordering::Relaxed);
+        self.stats.throttled.store(0, Ordering::Relaxed);
+        self.stats.server_errors.store(0, Ordering::Relaxed);
+        self.stats.cost_milli.store(0, Ordering::Relaxed);
+    }
+
+    /// Sends a GET request, retrying throttled (403/429), server (5xx) and
+    /// network failures with exponential backoff.  Only successful responses
+    /// are returned.
+    async fn send(&self, url : &str) -> Result<Response, String> {
+        let _permit = self.limiter.acquire()
+            .await
+            .map_err(|e| err!(format!("API Request Failure\n{}",url),e))?;
+        let mut attempt = 0;
+        loop {
+            self.wait_for_rate_limit().await;
+            self.stats.requests.fetch_add(1, Ordering::Relaxed);
+            let failure = match self.client.get(url)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .send()
+                .await
+            {
+                Ok(res) => {
+                    self.record_rate_limit(&res);
+                    let status = res.status();
+                    if status.is_success() {
+                        return Ok(res);
+                    }
+                    let body = res.text().await.unwrap_or_default();
+                    if CanvasClient::is_throttled(status, &body) {
+                        self.stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    }
+                    else if status.is_server_error() {
+                        self.stats.server_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                    else {
+                        return Err(err!(format!("API Request Failure ({})\n{}",status,url),body));
+                    }
+                    format!("{} {}", status, body)
+                }
+                Err(e) => e.to_string()
+            };
+
+            if attempt >= MAX_RETRIES {
+                return Err(err!(format!("API Request Failure after {} retries\n{}",MAX_RETRIES,url),failure));
+            }
+            self.stats.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(CanvasClient::backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    fn is_throttled(status : StatusCode, body : &str) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS ||
+            (status == StatusCode::FORBIDDEN && body.contains("Rate Limit Exceeded"))
+    }
+
+    fn backoff(attempt : u32) -> Duration {
+        let delay = BACKOFF_BASE_MS.saturating_mul(1 << attempt.min(10));
+        Duration::from_millis(delay.min(BACKOFF_MAX_MS))
+    }
+
+    fn record_rate_limit(&self, res : &Response) {
+        let header_f64 = |name : &str| res.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok());
+        if let Some(cost) = header_f64("x-request-cost") {
+            self.stats.cost_milli.fetch_add((cost * 1000.0) as u64, Ordering::Relaxed);
+        }
+        if let Some(remaining) = header_f64("x-rate-limit-remaining") {
+            *self.rate_remaining.lock().unwrap() = Some(remaining);
+        }
+    }
+
+    /// Pauses before a request when the last known quota is running low.  The
+    /// lower the quota the longer the pause, giving the bucket time to refill.
+    async fn wait_for_rate_limit(&self) {
+        let remaining = *self.rate_remaining.lock().unwrap();
+        if let Some(remaining) = remaining {
+            if remaining < RATE_LIMIT_LOW_WATER {
+                let ratio = 1.0 - (remaining.max(0.0) / RATE_LIMIT_LOW_WATER);
+                let delay = (ratio * BACKOFF_MAX_MS as f64 / 4.0) as u64;
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+        }
+    }
 }
 
 pub async fn connect_database(config : &Config) -> Result<Pool<Postgres>, String> {
     PgPoolOptions::new()
                 .max_connections(config.general.postgres_pool as u32)
                 .connect(&config.general.postgres)
-                .await 
+                .await
                 .map_err(|e| err!("Postgress DB Connect Failure",e))
 }
 
+/// Brings the schema up to date by applying any of the versioned SQL files in
+/// `migrations/` that have not been applied yet.  Applied versions are tracked
+/// by sqlx in the `_sqlx_migrations` table.
+pub async fn run_migrations(database : &Pool<Postgres>) -> Result<(), String> {
+    sqlx::migrate!("./migrations")
+        .run(database)
+        .await
+        .map_err(|e| err!("Postgress DB Migration Failure",e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+    use rust_decimal::Decimal;
+    use axum::{Router, routing::get};
+    use axum::http::{StatusCode as HttpStatus, HeaderMap};
+    use crate::data::student::Student;
+
+    fn config(server : &str) -> Config {
+        toml::from_str(&format!(r#"
+            [general]
+            server = "{}"
+            token = "unused"
+            postgres = "unused"
+            postgres_pool = 1
+            api_concurrency = 1
+
+            [current_config]
+            exclude_zero_grades = false
+            courses = [["CSE110", 1001]]
+        "#, server)).unwrap()
+    }
+
+    async fn serve(app : Router) -> CanvasClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        CanvasClient::new(&config(&format!("http://{}", addr)), ApiMode::Live).unwrap()
+    }
+
+    /// Fails the first `failures` requests with `status` and `body`, then
+    /// answers with an empty object.
+    fn flaky(failures : usize, status : HttpStatus, body : &'static str) -> Router {
+        let calls = Arc::new(AtomicUsize::new(0));
+        Router::new().route("/api/v1/test", get(move || {
+            let calls = calls.clone();
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                    (status, body.to_string())
+                }
+                else {
+                    (HttpStatus::OK, "{}".to_string())
+                }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_until_success() {
+        let client = serve(flaky(2, HttpStatus::BAD_GATEWAY, "down")).await;
+        let res : Result<serde_json::Value, String> = client.json_api_get_single("api/v1/test?a=1").await;
+        assert!(res.is_ok());
+        let stats = client.stats();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.retries, 2);
+        assert_eq!(stats.server_errors, 2);
+    }
+
+    #[tokio::test]
+    async fn retries_give_up_after_max_retries() {
+        let client = serve(flaky(usize::MAX, HttpStatus::SERVICE_UNAVAILABLE, "down")).await;
+        let res : Result<serde_json::Value, String> = client.json_api_get_single("api/v1/test?a=1").await;
+        assert!(res.is_err());
+        let stats = client.stats();
+        assert_eq!(stats.requests, MAX_RETRIES as u64 + 1);
+        assert_eq!(stats.retries, MAX_RETRIES as u64);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_403_is_throttled_and_retried() {
+        let client = serve(flaky(1, HttpStatus::FORBIDDEN, "403 Forbidden (Rate Limit Exceeded)")).await;
+        let res : Result<serde_json::Value, String> = client.json_api_get_single("api/v1/test?a=1").await;
+        assert!(res.is_ok());
+        let stats = client.stats();
+        assert_eq!(stats.throttled, 1);
+        assert_eq!(stats.retries, 1);
+    }
+
+    #[tokio::test]
+    async fn plain_403_fails_without_retry() {
+        let client = serve(flaky(1, HttpStatus::FORBIDDEN, "unauthorized")).await;
+        let res : Result<serde_json::Value, String> = client.json_api_get_single("api/v1/test?a=1").await;
+        assert!(res.is_err());
+        let stats = client.stats();
+        assert_eq!(stats.requests, 1);
+        assert_eq!(stats.retries, 0);
+        assert_eq!(stats.throttled, 0);
+    }
+
+    #[tokio::test]
+    async fn low_quota_slows_requests_down() {
+        let app = Router::new().route("/api/v1/test", get(|| async {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-rate-limit-remaining", "0.0".parse().unwrap());
+            headers.insert("x-request-cost", "1.5".parse().unwrap());
+            (headers, "{}")
+        }));
+        let client = serve(app).await;
+        let _ : serde_json::Value = client.json_api_get_single("api/v1/test?a=1").await.unwrap();
+        assert_eq!(client.stats().rate_remaining, Some(0.0));
+
+        // With the quota exhausted the next request waits the full slowdown
+        let start = Instant::now();
+        let _ : serde_json::Value = client.json_api_get_single("api/v1/test?a=1").await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(BACKOFF_MAX_MS / 4));
+        assert!((client.stats().cost - 3.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn replay_follows_pages() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+        let client = CanvasClient::new(&config("https://canvas.test"), ApiMode::Replay(dir)).unwrap();
+        let students = client.json_api_get::<Student>("/api/v1/courses/1001/users\
+            ?enrollment_type[]=student\
+            &include[]=total_scores\
+            &include[]=enrollments\
+            &enrollment_state[]=active\
+            &enrollment_state[]=invited\
+            &enrollment_state[]=completed").await.unwrap();
+        let test_student = client.json_api_get_single::<Student>(
+            "/api/v1/courses/1001/student_view_student?").await.unwrap();
+
+        // Two pages of users and the test student
+        assert_eq!(client.stats().requests, 3);
+        assert_eq!(test_student.id, 5999);
+        assert_eq!(students.iter().map(|x| (x.id, x.name.as_str())).collect::<Vec<_>>(),
+            vec![(5001, "Avery Sorensen"), (5002, "Casey Delgado"), (5003, "Dakota Calloway"), (5999, "Test Student")]);
+        let grades = &students[1].enrollments.as_ref().unwrap()[0].grades;
+        assert_eq!(grades.current_grade.as_deref(), Some("B-"));
+        assert_eq!(grades.current_score, Some(Decimal::new(8125, 2)));
+    }
+
+    #[tokio::test]
+    async fn replay_reports_missing_fixture() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+        let client = CanvasClient::new(&config("https://canvas.test"), ApiMode::Replay(dir)).unwrap();
+        let result = client.json_api_get::<serde_json::Value>("/api/v1/courses/1001/assignments?").await;
+        assert!(result.is_err_and(|e| e.contains("Fixture Missing")));
+    }
+}
@@ -1,7 +1,8 @@
-use sqlx::{Pool, Postgres};
+use sqlx::{Postgres, Transaction};
 use serde::Deserialize;
-use crate::data::connections::json_api_get_single;
-use crate::data::config::Config;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
 use crate::macros::err;
 
 #[derive(Deserialize)]
@@ -11,6 +12,8 @@ pub struct Course {
     pub concluded : bool,
     pub term : CourseTerm,
     pub total_students : Option<i32>,
+    pub apply_assignment_group_weights : Option<bool>,
+    pub grading_standard_id : Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -19,53 +22,21 @@ pub struct CourseTerm {
 }
 
 impl Course {
-    // pub async fn get_courses(config : &Config, current : bool) -> io::Result<Vec<Self>> {
-    //     let mut courses = json_api_get::<Course>(config, 
-    //         "/api/v1/courses\
-    //                 ?enrollment_type=teacher\
-    //                 &state=available\
-    //                 &include[]=concluded\
-    //                 &include[]=term\
-    //                 &include[]=total_students").await?;
-    //     courses.iter_mut().for_each(|x| x.term.name = 
-    //         Course::convert_term(&x.term.name));
-    //     courses.retain(|x| !x.term.name.is_empty());
-    //     if current {
-    //         courses.retain(|x| !x.concluded);
-    //     }
-    //     courses.sort_by(|x, y| x.course_code.cmp(&y.course_code));
-    //     Ok(courses)
-    // }
-
-    pub async fn create_table(database : &Pool<Postgres>) -> Result<(), String> {
-       sqlx::query(
-        "
-            CREATE TABLE curr_courses(
-                id INT,
-                code TEXT,
-                concluded BOOLEAN,
-                term TEXT,
-                students INT
-            );
-        ")
-        .execute(database)
-        .await
-        .map_err(|e| err!("SQL Course Table Creation Failure",e))?;
-        Ok(())
-    }
-
-    pub async fn drop_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query("DROP TABLE IF EXISTS curr_courses;")
-            .execute(database)
+    /// Removes courses that are no longer configured, along with (through
+    /// the foreign keys) their assignments, students and submissions.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32]) -> Result<(), String> {
+        sqlx::query("DELETE FROM curr_courses WHERE NOT (id = ANY($1));")
+            .bind(course_ids)
+            .execute(&mut **tx)
             .await
-            .map_err(|e| err!("SQL Course Table Drop Failure",e))?;
+            .map_err(|e| err!("SQL Course Table Cleanup Failure",e))?;
         Ok(())
     }
 
-    pub async fn load(database : &Pool<Postgres>, config : &Config, course_ids : &[i32]) -> Result<(),String> {
+    pub async fn fetch(client : &CanvasClient, course_ids : &[i32]) -> Result<Vec<Self>,String> {
         let mut courses = Vec::<Self>::new();
         for id in course_ids.iter() {
-            let course = json_api_get_single(config, 
+            let course = client.json_api_get_single(
                 &format!("/api/v1/courses/{}\
                                  ?include[]=term\
                                  &include[]=concluded\
@@ -73,29 +44,33 @@ impl Course {
                                  .await?;
             courses.push(course)
         }
+        Ok(courses)
+    }
 
-        for c in courses.iter() {
-            sqlx::query(
-                "
-                INSERT INTO curr_courses
-                (id, code, concluded, term, students)
-                 VALUES
-                ($1, $2, $3, $4, $5);
-            ")
-            .bind(c.id)
-            .bind(c.course_code.clone())
-            .bind(c.concluded)
-            .bind(Course::convert_term(&c.term.name))
-            .bind(c.total_students.unwrap_or(0))
-            .execute(database)
-            .await
-            .map_err(|e| err!("Course SQL Failure", e))?;
-        }
-        Ok(())
+    /// Lists the concluded courses the user teaches that belong to a regular
+    /// term, sorted by course code.
+    pub async fn fetch_concluded(client : &CanvasClient) -> Result<Vec<Self>,String> {
+        let mut courses = client.json_api_get::<Course>(
+            "/api/v1/courses\
+            ?enrollment_type=teacher\
+            &state[]=available\
+            &state[]=completed\
+            &include[]=concluded\
+            &include[]=term\
+            &include[]=total_students")
+            .await?;
+        courses.retain(|x| x.concluded && !Course::convert_term(&x.term.name).is_empty());
+        courses.sort_by(|x, y| x.course_code.cmp(&y.course_code));
+        Ok(courses)
     }
 
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, courses : &[Self]) -> Result<BulkStats,String> {
+        bulk_insert(tx, courses).await
+    }
 
-    fn convert_term(term : &str) -> String {
+    /// Turns a Canvas term name like "Fall 2024" into "2024-4F", which sorts
+    /// chronologically.  Empty for terms that are not a semester.
+    pub fn convert_term(term : &str) -> String {
         let mut parts = term.split_whitespace();
         let season = parts.next().unwrap_or("");
         let year = parts.next().unwrap_or("");
@@ -108,4 +83,34 @@ impl Course {
         };
         format!("{}-{}",year,period)
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl BulkInsert for Course {
+    const TABLE : &'static str = "curr_courses";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_courses
+            (id, code, concluded, term, students, weighted)
+            SELECT * FROM UNNEST($1::INT[], $2::TEXT[], $3::BOOLEAN[], $4::TEXT[], $5::INT[], $6::BOOLEAN[])
+            ON CONFLICT (id) DO UPDATE SET
+                code = EXCLUDED.code,
+                concluded = EXCLUDED.concluded,
+                term = EXCLUDED.term,
+                students = EXCLUDED.students,
+                weighted = EXCLUDED.weighted;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_code.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.concluded).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| Course::convert_term(&x.term.name)).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.total_students.unwrap_or(0)).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.apply_assignment_group_weights.unwrap_or(false)).collect::<Vec<bool>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Course SQL Failure", e))?;
+        Ok(())
+    }
+}
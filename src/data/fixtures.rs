@@ -0,0 +1,121 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use reqwest::Url;
+use crate::data::config::Config;
+use crate::macros::err;
+
+/// Where Canvas API responses come from.  `Record` talks to Canvas and saves
+/// every response to the fixture directory; `Replay` serves the saved
+/// responses and never touches the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiMode {
+    Live,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl ApiMode {
+
+    /// Reads `general.api_mode` and `general.fixtures` from the config.
+    pub fn from_config(config : &Config) -> Result<Self, String> {
+        let dir = PathBuf::from(config.general.fixtures.clone().unwrap_or("fixtures".to_string()));
+        match config.general.api_mode.as_deref() {
+            None | Some("live") => Ok(ApiMode::Live),
+            Some("record") => Ok(ApiMode::Record(dir)),
+            Some("replay") => Ok(ApiMode::Replay(dir)),
+            Some(other) => Err(err!("Invalid API Mode",
+                format!("{} (expected live, record or replay)", other)))
+        }
+    }
+
+    /// Applies `--record <dir>` or `--replay <dir>` from the command line,
+    /// which take precedence over the config.
+    pub fn from_args(args : &[String], default : ApiMode) -> Result<Self, String> {
+        let mut mode = default;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let record = match arg.as_str() {
+                "--record" => true,
+                "--replay" => false,
+                _ => continue
+            };
+            let dir = iter.next()
+                .ok_or(err!("Invalid Command Line", format!("{} requires a fixture directory", arg)))?;
+            mode = if record {
+                ApiMode::Record(PathBuf::from(dir))
+            }
+            else {
+                ApiMode::Replay(PathBuf::from(dir))
+            };
+        }
+        Ok(mode)
+    }
+}
+
+/// One recorded API response.  The body is kept as JSON (rather than a raw
+/// string) so fixtures can be read and anonymized by hand.
+#[derive(Serialize, Deserialize)]
+pub struct Fixture {
+    pub url : String,
+    pub link : Option<String>,
+    pub body : serde_json::Value,
+}
+
+impl Fixture {
+
+    pub fn load(dir : &Path, url : &Url) -> Result<Self, String> {
+        let path = Fixture::path(dir, url);
+        let file = File::open(&path)
+            .map_err(|e| err!(format!("Fixture Missing\n{}\n{}", url, path.display()), e))?;
+        let mut reader = BufReader::new(file);
+        let mut buffer = String::new();
+        reader.read_to_string(&mut buffer)
+            .map_err(|e| err!(format!("Fixture Read Failure\n{}", path.display()), e))?;
+        serde_json::from_str(&buffer)
+            .map_err(|e| err!(format!("Fixture Parsing Failure\n{}", path.display()), e))
+    }
+
+    pub fn save(&self, dir : &Path, url : &Url) -> Result<(), String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| err!(format!("Fixture Directory Failure\n{}", dir.display()), e))?;
+        let path = Fixture::path(dir, url);
+        let file = File::create(&path)
+            .map_err(|e| err!(format!("Fixture File Failure\n{}", path.display()), e))?;
+        let mut writer = BufWriter::new(file);
+        let buffer = serde_json::to_string_pretty(self)
+            .map_err(|e| err!("Fixture Conversion Failure", e))?;
+        writer.write_all(buffer.as_bytes())
+            .map_err(|e| err!(format!("Fixture Write Failure\n{}", path.display()), e))?;
+        Ok(())
+    }
+
+    /// Fixtures are keyed by path and query only, so a recording made against
+    /// one server replays against any other (including the mock server).
+    pub fn key(url : &Url) -> String {
+        match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string()
+        }
+    }
+
+    fn path(dir : &Path, url : &Url) -> PathBuf {
+        let key = Fixture::key(url);
+        let readable = url.path()
+            .trim_matches('/')
+            .replace('/', "_");
+        dir.join(format!("{}-{:016x}.json", readable, Fixture::hash(&key)))
+    }
+
+    // FNV-1a, chosen because it is stable across Rust releases (unlike the
+    // standard library's hasher) so fixture names never change.
+    fn hash(key : &str) -> u64 {
+        let mut hash : u64 = 0xcbf29ce484222325;
+        for byte in key.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
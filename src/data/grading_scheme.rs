@@ -0,0 +1,174 @@
+use std::fmt;
+use serde::Deserialize;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use sqlx::{Pool, Postgres, Transaction};
+use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+// Canvas uses this scheme when grading is enabled without picking a standard
+const DEFAULT_SCHEME : [(&str, i64); 12] = [
+    ("A", 94), ("A-", 90), ("B+", 87), ("B", 84), ("B-", 80), ("C+", 77),
+    ("C", 74), ("C-", 70), ("D+", 67), ("D", 64), ("D-", 61), ("F", 0)
+];
+
+#[derive(Deserialize)]
+struct GradingStandard {
+    grading_scheme : Vec<SchemeEntry>,
+}
+
+#[derive(Deserialize)]
+struct SchemeEntry {
+    name : String,
+    // Lower bound as a fraction of the total, 0.94 for 94%
+    value : Decimal,
+}
+
+/// One letter grade of a course's scheme, stored as a percentage.
+pub struct GradeCutoff {
+    pub course_id : i32,
+    pub name : String,
+    pub min_score : Decimal,
+}
+
+impl GradeCutoff {
+    pub async fn fetch(client : &CanvasClient, course_id : i32, standard_id : Option<i32>) -> Result<Vec<Self>, String> {
+        let Some(standard_id) = standard_id else {
+            return Ok(DEFAULT_SCHEME.iter()
+                .map(|x| GradeCutoff { course_id, name : x.0.to_string(), min_score : Decimal::from(x.1) })
+                .collect());
+        };
+        let standard = client.json_api_get_single::<GradingStandard>(&format!(
+            "/api/v1/courses/{}/grading_standards/{}", course_id, standard_id))
+            .await?;
+        Ok(standard.grading_scheme.into_iter()
+            .map(|x| GradeCutoff {
+                course_id,
+                name : x.name,
+                min_score : (x.value * Decimal::ONE_HUNDRED).normalize()
+            })
+            .collect())
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, cutoffs : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, cutoffs).await
+    }
+
+    /// Removes letters of the refreshed courses that are no longer in their
+    /// scheme.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], cutoffs : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM curr_grading_schemes AS sch
+            WHERE sch.course_id = ANY($1) AND NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::TEXT[]) AS keep(course_id, name)
+                WHERE keep.course_id = sch.course_id AND keep.name = sch.name);
+        ")
+        .bind(course_ids)
+        .bind(cutoffs.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(cutoffs.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Grading Scheme Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for GradeCutoff {
+    const TABLE : &'static str = "curr_grading_schemes";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_grading_schemes
+            (course_id, name, min_score)
+            SELECT * FROM UNNEST($1::INT[], $2::TEXT[], $3::NUMERIC[])
+            ON CONFLICT (course_id, name) DO UPDATE SET
+                min_score = EXCLUDED.min_score;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.min_score).collect::<Vec<Decimal>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Grading Scheme SQL Failure", e))?;
+        Ok(())
+    }
+}
+
+/// The letters of a course from highest to lowest, each with the lowest
+/// percentage that earns it.
+#[derive(Clone)]
+pub struct GradingScheme {
+    pub cutoffs : Vec<(String, Decimal)>,
+    // Where the scheme came from, shown in report headers
+    pub source : &'static str,
+}
+
+impl GradingScheme {
+    pub fn new(mut cutoffs : Vec<(String, Decimal)>, source : &'static str) -> Self {
+        cutoffs.sort_by_key(|x| std::cmp::Reverse(x.1));
+        Self { cutoffs, source }
+    }
+
+    /// The scheme stored by the last refresh, or Canvas's default scheme if
+    /// the course has not been refreshed.
+    pub async fn load(database : &Pool<Postgres>, course_id : i32) -> Result<Self, String> {
+        let cutoffs = sqlx::query_as::<_,(String, Decimal)>(
+                "
+                SELECT name, min_score
+                FROM curr_grading_schemes
+                WHERE course_id = $1;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Grading Scheme SQL Query Failure",e))?;
+        if cutoffs.is_empty() {
+            return Ok(GradingScheme::new(DEFAULT_SCHEME.iter()
+                .map(|x| (x.0.to_string(), Decimal::from(x.1)))
+                .collect(), "default"));
+        }
+        Ok(GradingScheme::new(cutoffs, "canvas"))
+    }
+
+    /// The course's letter grades: the override from config.toml if there is
+    /// one, otherwise the Canvas grading standard saved by the last refresh.
+    pub async fn for_course(database : &Pool<Postgres>, config : &Config, course_id : i32) -> Result<Self, String> {
+        let configured = config.current_config.courses.iter()
+            .find(|x| x.1 == course_id)
+            .and_then(|x| config.current_config.grading_schemes.as_ref()?.get(&x.0));
+        match configured {
+            Some(cutoffs) => Ok(GradingScheme::new(cutoffs.clone(), "config")),
+            None => GradingScheme::load(database, course_id).await
+        }
+    }
+
+    /// Index into `cutoffs` of the letter earned by `percent`.  Anything
+    /// below the lowest cutoff gets the lowest letter.
+    pub fn bucket(&self, percent : Decimal) -> usize {
+        self.cutoffs.iter()
+            .position(|x| percent >= x.1)
+            .unwrap_or(self.cutoffs.len().saturating_sub(1))
+    }
+
+    pub fn letter(&self, percent : Decimal) -> &str {
+        self.cutoffs.get(self.bucket(percent))
+            .map(|x| x.0.as_str())
+            .unwrap_or("")
+    }
+}
+
+impl fmt::Display for GradingScheme {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Grading scheme: {} ({})",
+            self.cutoffs.iter()
+                .map(|x| format!("{} {}", x.0, x.1.normalize()))
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.source)
+    }
+}
@@ -0,0 +1,230 @@
+use sqlx::{Pool, Postgres, Transaction};
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::assignment::Assignment;
+use crate::data::assignment_group::AssignmentGroup;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::course::Course;
+use crate::data::student::Student;
+use crate::data::submission::Submission;
+use crate::macros::err;
+
+/// A concluded course kept in the hist_* tables, keyed by its term.
+pub struct HistCourse {
+    pub id : i32,
+    pub code : String,
+    pub term : String,
+    pub students : i32,
+}
+
+pub struct HistStudent {
+    pub course_id : i32,
+    pub id : i32,
+    pub name : String,
+    pub final_grade : Option<String>,
+    pub final_score : Option<Decimal>,
+}
+
+pub struct HistAssignment {
+    pub course_id : i32,
+    pub id : i32,
+    pub name : String,
+    pub group_name : String,
+    pub points_possible : Decimal,
+}
+
+pub struct HistSubmission {
+    pub course_id : i32,
+    pub assignment_id : i32,
+    pub user_id : i32,
+    pub score : Option<Decimal>,
+    pub excused : bool,
+    pub missing : bool,
+}
+
+impl HistCourse {
+    pub fn from_course(course : &Course) -> Self {
+        Self {
+            id : course.id,
+            code : course.course_code.clone(),
+            term : Course::convert_term(&course.term.name),
+            students : course.total_students.unwrap_or(0),
+        }
+    }
+
+    /// Ids of the courses already in the archive.
+    pub async fn archived(database : &Pool<Postgres>) -> Result<Vec<i32>, String> {
+        sqlx::query_scalar::<_,i32>("SELECT id FROM hist_courses;")
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("History SQL Query Failure",e))
+    }
+
+    /// Archives courses, first removing (through the foreign keys) anything
+    /// archived for them before.
+    pub async fn replace(tx : &mut Transaction<'_, Postgres>, courses : &[Self]) -> Result<BulkStats, String> {
+        sqlx::query("DELETE FROM hist_courses WHERE id = ANY($1);")
+            .bind(courses.iter().map(|x| x.id).collect::<Vec<i32>>())
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| err!("SQL History Table Cleanup Failure",e))?;
+        bulk_insert(tx, courses).await
+    }
+}
+
+impl HistStudent {
+    pub fn from_student(student : &Student) -> Self {
+        let (final_grade, final_score) = student.final_grade();
+        Self {
+            course_id : student.course_id,
+            id : student.id,
+            name : student.name.clone(),
+            final_grade,
+            final_score,
+        }
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, students : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, students).await
+    }
+}
+
+impl HistAssignment {
+    pub fn from_assignment(assignment : &Assignment, groups : &[AssignmentGroup]) -> Self {
+        Self {
+            course_id : assignment.course_id,
+            id : assignment.id,
+            name : assignment.name.clone(),
+            group_name : groups.iter()
+                .find(|x| x.id == assignment.assignment_group_id)
+                .map(|x| x.name.clone())
+                .unwrap_or_default(),
+            points_possible : assignment.points_possible,
+        }
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, assignments : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, assignments).await
+    }
+}
+
+impl HistSubmission {
+    pub fn from_submission(course_id : i32, submission : &Submission) -> Self {
+        Self {
+            course_id,
+            assignment_id : submission.assignment_id,
+            user_id : submission.user_id,
+            score : submission.score,
+            excused : submission.excused.unwrap_or(false),
+            missing : submission.missing.unwrap_or(false),
+        }
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, submissions : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, submissions).await
+    }
+}
+
+#[async_trait]
+impl BulkInsert for HistCourse {
+    const TABLE : &'static str = "hist_courses";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO hist_courses
+            (id, code, term, students)
+            SELECT * FROM UNNEST($1::INT[], $2::TEXT[], $3::TEXT[], $4::INT[]);
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.code.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.term.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.students).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("History Course SQL Failure", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for HistStudent {
+    const TABLE : &'static str = "hist_students";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO hist_students
+            (course_id, id, name, final_grade, final_score)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::TEXT[], $5::NUMERIC[])
+            ON CONFLICT (course_id, id) DO UPDATE SET
+                name = EXCLUDED.name,
+                final_grade = EXCLUDED.final_grade,
+                final_score = EXCLUDED.final_score;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.final_grade.clone()).collect::<Vec<Option<String>>>())
+        .bind(rows.iter().map(|x| x.final_score).collect::<Vec<Option<Decimal>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("History Student SQL Failure", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for HistAssignment {
+    const TABLE : &'static str = "hist_assignments";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO hist_assignments
+            (course_id, id, name, group_name, points_possible)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::TEXT[], $5::NUMERIC[])
+            ON CONFLICT (course_id, id) DO UPDATE SET
+                name = EXCLUDED.name,
+                group_name = EXCLUDED.group_name,
+                points_possible = EXCLUDED.points_possible;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.group_name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.points_possible).collect::<Vec<Decimal>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("History Assignment SQL Failure", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for HistSubmission {
+    const TABLE : &'static str = "hist_submissions";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO hist_submissions
+            (course_id, assignment_id, user_id, score, excused, missing)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::INT[], $4::NUMERIC[], $5::BOOLEAN[], $6::BOOLEAN[])
+            ON CONFLICT (course_id, assignment_id, user_id) DO UPDATE SET
+                score = EXCLUDED.score,
+                excused = EXCLUDED.excused,
+                missing = EXCLUDED.missing;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.assignment_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.score).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.excused).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.missing).collect::<Vec<bool>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("History Submission SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use reqwest::Url;
+use crate::macros::err;
+
+/// Pagination links from a Canvas `Link` header (RFC 8288).  Canvas uses
+/// numbered pages for some endpoints and opaque bookmarks for others, so the
+/// URLs are kept exactly as sent and followed without inspecting them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PageLinks {
+    pub current : Option<String>,
+    pub next : Option<String>,
+    pub prev : Option<String>,
+    pub first : Option<String>,
+    pub last : Option<String>,
+}
+
+impl PageLinks {
+
+    /// Parses a `Link` header value.  Relative targets are resolved against
+    /// `base` (the URL of the page that returned the header).  Links without
+    /// a rel, and relation types other than the five used for pagination,
+    /// are ignored.
+    pub fn parse(header : &str, base : &Url) -> Result<Self, String> {
+        let mut links = PageLinks::default();
+        let mut parser = LinkParser { input : header, pos : 0 };
+        parser.skip_separators();
+        while !parser.at_end() {
+            let target = parser.target()
+                .map_err(|e| err!("Link Header Parsing Failure", format!("{}\n{}",e,header)))?;
+            let rels = parser.params()
+                .map_err(|e| err!("Link Header Parsing Failure", format!("{}\n{}",e,header)))?;
+            let Some(rels) = rels else {
+                parser.skip_separators();
+                continue;
+            };
+            let url = base.join(&target)
+                .map_err(|e| err!("Link Header URL Failure", format!("{}\n{}",e,target)))?
+                .to_string();
+            for rel in rels.split_whitespace() {
+                let slot = match rel.to_ascii_lowercase().as_str() {
+                    "current" => &mut links.current,
+                    "next" => &mut links.next,
+                    "prev" | "previous" => &mut links.prev,
+                    "first" => &mut links.first,
+                    "last" => &mut links.last,
+                    _ => continue
+                };
+                if slot.is_none() {
+                    *slot = Some(url.clone());
+                }
+            }
+            parser.skip_separators();
+        }
+        Ok(links)
+    }
+}
+
+struct LinkParser<'a> {
+    input : &'a str,
+    pos : usize,
+}
+
+impl LinkParser<'_> {
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t') | Some(',')) {
+            self.pos += 1;
+        }
+    }
+
+    // link-value = "<" URI-Reference ">" *( OWS ";" OWS link-param )
+    fn target(&mut self) -> Result<String, String> {
+        if self.bump() != Some('<') {
+            return Err(format!("Expected '<' at position {}", self.pos));
+        }
+        let start = self.pos;
+        let Some(len) = self.input[start..].find('>') else {
+            return Err("Unterminated link target".to_string());
+        };
+        self.pos = start + len + 1;
+        Ok(self.input[start..start + len].trim().to_string())
+    }
+
+    // Returns the value of the rel parameter, if there is one, consuming
+    // every parameter of the current link-value.
+    fn params(&mut self) -> Result<Option<String>, String> {
+        let mut rel = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some(',') => break,
+                Some(';') => { self.pos += 1; }
+                Some(c) => return Err(format!("Unexpected '{}' at position {}", c, self.pos))
+            }
+            self.skip_whitespace();
+            let name = self.token();
+            if name.is_empty() {
+                // Tolerate a trailing ';'
+                continue;
+            }
+            self.skip_whitespace();
+            let mut value = String::new();
+            if self.peek() == Some('=') {
+                self.pos += 1;
+                self.skip_whitespace();
+                value = if self.peek() == Some('"') {
+                    self.quoted()?
+                }
+                else {
+                    self.token()
+                };
+            }
+            // Only the first occurrence of rel counts (RFC 8288 Section 3.3)
+            if name.eq_ignore_ascii_case("rel") && rel.is_none() {
+                rel = Some(value);
+            }
+        }
+        Ok(rel)
+    }
+
+    fn token(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || matches!(c, ';' | ',' | '=' | '"') {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        self.input[start..self.pos].to_string()
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some(c) => value.push(c),
+                    None => break
+                },
+                Some(c) => value.push(c),
+                None => break
+            }
+        }
+        Err("Unterminated quoted string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://canvas.example.edu/api/v1/courses/1/users?page=2").unwrap()
+    }
+
+    #[test]
+    fn next_listed_last() {
+        let links = PageLinks::parse(
+            "<https://canvas.example.edu/api/v1/courses/1/users?page=1>; rel=\"first\", \
+             <https://canvas.example.edu/api/v1/courses/1/users?page=3>; rel=\"next\"", &base()).unwrap();
+        assert_eq!(links.first.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=1"));
+        assert_eq!(links.next.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=3"));
+        assert_eq!(links.last, None);
+    }
+
+    #[test]
+    fn quoted_rel_with_several_values() {
+        let links = PageLinks::parse(
+            "<https://canvas.example.edu/api/v1/courses/1/users?page=3>; rel=\"next last\"", &base()).unwrap();
+        assert_eq!(links.next.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=3"));
+        assert_eq!(links.last, links.next);
+    }
+
+    #[test]
+    fn relative_urls_resolved_against_base() {
+        let links = PageLinks::parse(
+            "</api/v1/courses/1/users?page=3>; rel=next, <?page=1>; rel=prev", &base()).unwrap();
+        assert_eq!(links.next.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=3"));
+        assert_eq!(links.prev.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=1"));
+    }
+
+    #[test]
+    fn separators_inside_target() {
+        let links = PageLinks::parse(
+            "<https://canvas.example.edu/api/v1/courses/1/users?page=bookmark:a;b,c>; rel=\"next\", \
+             <https://canvas.example.edu/api/v1/courses/1/users?page=first>; rel=\"first\"", &base()).unwrap();
+        assert_eq!(links.next.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=bookmark:a;b,c"));
+        assert_eq!(links.first.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=first"));
+    }
+
+    #[test]
+    fn link_without_rel_is_ignored() {
+        let links = PageLinks::parse(
+            "<https://canvas.example.edu/help>; title=\"Help\", \
+             <https://canvas.example.edu/api/v1/courses/1/users?page=3>; rel=\"next\"", &base()).unwrap();
+        assert_eq!(links.next.as_deref(), Some("https://canvas.example.edu/api/v1/courses/1/users?page=3"));
+        assert_eq!(links, PageLinks { next : links.next.clone(), ..PageLinks::default() });
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        for header in ["https://canvas.example.edu/page=2; rel=next",
+                       "<https://canvas.example.edu/page=2; rel=next",
+                       "<https://canvas.example.edu/page=2> rel=next",
+                       "<https://canvas.example.edu/page=2>; rel=\"next"] {
+            assert!(PageLinks::parse(header, &base()).is_err(), "{}", header);
+        }
+    }
+}
@@ -1,6 +1,20 @@
+pub mod bulk;
 pub mod connections;
+pub mod fixtures;
+pub mod links;
 pub mod config;
 pub mod course;
 pub mod student;
 pub mod assignment;
+pub mod assignment_group;
+pub mod attempt;
+pub mod attendance;
+pub mod quiz_attempt;
+pub mod grading_scheme;
+pub mod history;
+pub mod outcome;
+pub mod outcome_result;
+pub mod section;
+pub mod snapshot;
 pub mod submission;
+pub mod sync;
@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, Transaction};
+use serde::Deserialize;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{array_literals, bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+#[derive(Deserialize)]
+struct OutcomeLink {
+    outcome : Outcome,
+}
+
+/// A learning outcome linked into a course.  Canvas rolls a student's
+/// results for the outcome up into one score using `calculation_method`.
+#[derive(Deserialize, sqlx::FromRow, Clone)]
+pub struct Outcome {
+    #[serde(default)]
+    pub course_id : i32,
+    pub id : i32,
+    pub title : String,
+    pub description : Option<String>,
+    pub mastery_points : Option<Decimal>,
+    pub points_possible : Option<Decimal>,
+    pub calculation_method : Option<String>,
+    pub calculation_int : Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct Rubric {
+    id : i32,
+    title : String,
+    data : Option<Vec<RubricCriterion>>,
+    associations : Option<Vec<RubricAssociation>>,
+}
+
+#[derive(Deserialize)]
+struct RubricCriterion {
+    id : String,
+    learning_outcome_id : Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct RubricAssociation {
+    association_id : i32,
+    association_type : String,
+}
+
+/// A rubric criterion that assesses an outcome, with the assignments
+/// graded by the rubric.
+pub struct RubricLink {
+    pub course_id : i32,
+    pub rubric_id : i32,
+    pub criterion_id : String,
+    pub outcome_id : i32,
+    pub rubric_title : String,
+    pub assignment_ids : Vec<i32>,
+}
+
+impl Outcome {
+    /// Downloads every outcome linked anywhere in the course's outcome groups.
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let links = client.json_api_get::<OutcomeLink>(&format!(
+            "/api/v1/courses/{}/outcome_group_links?outcome_style=full", course_id))
+            .await?;
+        Ok(links.into_iter()
+            .map(|x| Outcome { course_id, ..x.outcome })
+            .collect())
+    }
+
+    pub async fn load(database : &Pool<Postgres>, course_id : i32) -> Result<Vec<Self>, String> {
+        sqlx::query_as::<_,Outcome>(
+                "
+                SELECT course_id, id, title, description, mastery_points, points_possible,
+                    calculation_method, calculation_int
+                FROM out_outcomes
+                WHERE course_id = $1
+                ORDER BY title, id;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, outcomes : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, outcomes).await
+    }
+
+    /// Removes outcomes no longer linked in the refreshed courses, and
+    /// everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], outcomes : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM out_outcomes AS out
+            WHERE NOT (out.course_id = ANY($1)) OR NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[]) AS keep(course_id, id)
+                WHERE keep.course_id = out.course_id AND keep.id = out.id);
+        ")
+        .bind(course_ids)
+        .bind(outcomes.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(outcomes.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Outcome Table Cleanup Failure",e))?;
+        Ok(())
+    }
+
+    /// Rolls one student's results up into a score and whether the outcome
+    /// is mastered, following the outcome's calculation method:
+    ///   highest, latest, average,
+    ///   decaying_average - latest weighted calculation_int%, the average of
+    ///                      the rest the remainder (Canvas's default, 65%),
+    ///   n_mastery        - mastered after calculation_int results at mastery,
+    ///                      scored as the average of those results.
+    /// None when there are no results.
+    pub fn rollup(&self, results : &[(Option<DateTime<Utc>>, Decimal)]) -> Option<(Decimal, bool)> {
+        if results.is_empty() {
+            return None;
+        }
+        let mastery = self.mastery_points.unwrap_or_default();
+        let mut ordered = results.to_vec();
+        ordered.sort_by_key(|x| x.0);
+        let scores = ordered.iter().map(|x| x.1).collect::<Vec<Decimal>>();
+        let average = |x : &[Decimal]| x.iter().sum::<Decimal>() / Decimal::from(x.len());
+        let latest = *scores.last()?;
+
+        let score = match self.calculation_method.as_deref().unwrap_or("decaying_average") {
+            "highest" => scores.iter().copied().max()?,
+            "latest" => latest,
+            "average" => average(&scores),
+            "n_mastery" => {
+                let needed = self.calculation_int.unwrap_or(1).max(1) as usize;
+                let mastered = scores.iter().copied()
+                    .filter(|x| *x >= mastery)
+                    .collect::<Vec<Decimal>>();
+                if mastered.len() >= needed {
+                    return Some((average(&mastered), true));
+                }
+                return Some((average(&scores), false));
+            }
+            _ => {
+                if scores.len() == 1 {
+                    latest
+                }
+                else {
+                    let weight = Decimal::from(self.calculation_int.unwrap_or(65)) / Decimal::ONE_HUNDRED;
+                    latest * weight + average(&scores[..scores.len() - 1]) * (Decimal::ONE - weight)
+                }
+            }
+        };
+        Some((score, score >= mastery))
+    }
+}
+
+impl RubricLink {
+    /// Downloads the course's rubrics and keeps the criteria aligned with an
+    /// outcome.
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let rubrics = client.json_api_get::<Rubric>(&format!(
+            "/api/v1/courses/{}/rubrics?include[]=associations", course_id))
+            .await?;
+        let mut links = Vec::<RubricLink>::new();
+        for rubric in rubrics {
+            let assignment_ids = rubric.associations.unwrap_or_default().iter()
+                .filter(|x| x.association_type == "Assignment")
+                .map(|x| x.association_id)
+                .collect::<Vec<i32>>();
+            for criterion in rubric.data.unwrap_or_default() {
+                if let Some(outcome_id) = criterion.learning_outcome_id {
+                    links.push(RubricLink {
+                        course_id,
+                        rubric_id : rubric.id,
+                        criterion_id : criterion.id,
+                        outcome_id,
+                        rubric_title : rubric.title.clone(),
+                        assignment_ids : assignment_ids.clone(),
+                    });
+                }
+            }
+        }
+        Ok(links)
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, links : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, links).await
+    }
+
+    /// Removes criteria no longer aligned with an outcome in the refreshed
+    /// courses, and everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], links : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM out_rubric_links AS lnk
+            WHERE NOT (lnk.course_id = ANY($1)) OR NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[], $4::TEXT[]) AS keep(course_id, rubric_id, criterion_id)
+                WHERE keep.course_id = lnk.course_id AND keep.rubric_id = lnk.rubric_id
+                    AND keep.criterion_id = lnk.criterion_id);
+        ")
+        .bind(course_ids)
+        .bind(links.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(links.iter().map(|x| x.rubric_id).collect::<Vec<i32>>())
+        .bind(links.iter().map(|x| x.criterion_id.clone()).collect::<Vec<String>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Rubric Link Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for Outcome {
+    const TABLE : &'static str = "out_outcomes";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO out_outcomes
+            (course_id, id, title, description, mastery_points, points_possible,
+             calculation_method, calculation_int)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::TEXT[], $5::NUMERIC[], $6::NUMERIC[],
+                                 $7::TEXT[], $8::INT[])
+            ON CONFLICT (course_id, id) DO UPDATE SET
+                title = EXCLUDED.title,
+                description = EXCLUDED.description,
+                mastery_points = EXCLUDED.mastery_points,
+                points_possible = EXCLUDED.points_possible,
+                calculation_method = EXCLUDED.calculation_method,
+                calculation_int = EXCLUDED.calculation_int;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.title.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.description.clone()).collect::<Vec<Option<String>>>())
+        .bind(rows.iter().map(|x| x.mastery_points).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.points_possible).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.calculation_method.clone()).collect::<Vec<Option<String>>>())
+        .bind(rows.iter().map(|x| x.calculation_int).collect::<Vec<Option<i32>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Outcome SQL Failure", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for RubricLink {
+    const TABLE : &'static str = "out_rubric_links";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        let assignment_ids = array_literals(&rows.iter()
+            .map(|x| x.assignment_ids.clone())
+            .collect::<Vec<Vec<i32>>>());
+        sqlx::query(
+            "
+            INSERT INTO out_rubric_links
+            (course_id, rubric_id, criterion_id, outcome_id, rubric_title, assignment_ids)
+            SELECT course_id, rubric_id, criterion_id, outcome_id, rubric_title, assignment_ids::INT[]
+            FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::INT[], $5::TEXT[], $6::TEXT[])
+                AS l(course_id, rubric_id, criterion_id, outcome_id, rubric_title, assignment_ids)
+            ON CONFLICT (course_id, rubric_id, criterion_id) DO UPDATE SET
+                outcome_id = EXCLUDED.outcome_id,
+                rubric_title = EXCLUDED.rubric_title,
+                assignment_ids = EXCLUDED.assignment_ids;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.rubric_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.criterion_id.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.outcome_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.rubric_title.clone()).collect::<Vec<String>>())
+        .bind(assignment_ids)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Rubric Link SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use serde::Deserialize;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+#[derive(Deserialize)]
+struct CanvasResult {
+    id : i32,
+    score : Option<Decimal>,
+    possible : Option<Decimal>,
+    mastery : Option<bool>,
+    submitted_or_assessed_at : Option<DateTime<Utc>>,
+    links : ResultLinks,
+}
+
+// Canvas links results to users and outcomes with ids sent as strings
+#[derive(Deserialize)]
+struct ResultLinks {
+    user : String,
+    learning_outcome : String,
+    alignment : Option<String>,
+}
+
+/// One assessment of an outcome for a student, usually a rubric criterion
+/// scored while grading an assignment.
+pub struct OutcomeResult {
+    pub id : i32,
+    pub course_id : i32,
+    pub outcome_id : i32,
+    pub user_id : i32,
+    pub alignment : Option<String>,
+    pub score : Option<Decimal>,
+    pub possible : Option<Decimal>,
+    pub mastery : Option<bool>,
+    pub assessed_at : Option<DateTime<Utc>>,
+}
+
+impl OutcomeResult {
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let results = client.json_api_get_wrapped::<CanvasResult>(&format!(
+            "/api/v1/courses/{}/outcome_results?", course_id), "outcome_results")
+            .await?;
+        results.into_iter()
+            .map(|x| {
+                let parse = |id : &str| id.parse::<i32>()
+                    .map_err(|e| err!("Outcome Result Link Failure", e));
+                Ok(OutcomeResult {
+                    id : x.id,
+                    course_id,
+                    outcome_id : parse(&x.links.learning_outcome)?,
+                    user_id : parse(&x.links.user)?,
+                    alignment : x.links.alignment,
+                    score : x.score,
+                    possible : x.possible,
+                    mastery : x.mastery,
+                    assessed_at : x.submitted_or_assessed_at,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, results : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, results).await
+    }
+
+    /// Removes results no longer reported for the refreshed courses, and
+    /// everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], results : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM out_results
+            WHERE NOT (course_id = ANY($1)) OR NOT (id = ANY($2));
+        ")
+        .bind(course_ids)
+        .bind(results.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Outcome Result Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for OutcomeResult {
+    const TABLE : &'static str = "out_results";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO out_results
+            (id, course_id, outcome_id, user_id, alignment, score, possible, mastery, assessed_at)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::INT[], $4::INT[], $5::TEXT[],
+                                 $6::NUMERIC[], $7::NUMERIC[], $8::BOOLEAN[], $9::TIMESTAMPTZ[])
+            ON CONFLICT (id) DO UPDATE SET
+                course_id = EXCLUDED.course_id,
+                outcome_id = EXCLUDED.outcome_id,
+                user_id = EXCLUDED.user_id,
+                alignment = EXCLUDED.alignment,
+                score = EXCLUDED.score,
+                possible = EXCLUDED.possible,
+                mastery = EXCLUDED.mastery,
+                assessed_at = EXCLUDED.assessed_at;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.outcome_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.alignment.clone()).collect::<Vec<Option<String>>>())
+        .bind(rows.iter().map(|x| x.score).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.possible).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.mastery).collect::<Vec<Option<bool>>>())
+        .bind(rows.iter().map(|x| x.assessed_at).collect::<Vec<Option<DateTime<Utc>>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Outcome Result SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::attempt::Attempt;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+/// One finished attempt at a quiz.
+pub struct QuizAttempt {
+    pub quiz_id : i32,
+    pub user_id : i32,
+    pub attempt : i32,
+    pub course_id : i32,
+    pub assignment_id : i32,
+    pub score : Option<Decimal>,
+    pub points_possible : Decimal,
+    pub finished_at : Option<DateTime<Utc>>,
+}
+
+impl QuizAttempt {
+    /// Downloads the attempts of one quiz.  The quiz submissions endpoint
+    /// only returns each student's latest (or kept) attempt, so the attempts
+    /// come from the submission history of the quiz's assignment instead.
+    /// Attempts still in progress have not been submitted and are skipped.
+    pub async fn fetch(client : &CanvasClient, course_id : i32, quiz_id : i32,
+                       assignment_id : i32, points_possible : Decimal) -> Result<Vec<Self>, String> {
+        let attempts = Attempt::fetch_assignment(client, course_id, assignment_id).await?;
+        Ok(attempts.into_iter()
+            .filter(|x| x.submitted_at.is_some())
+            .map(|x| QuizAttempt {
+                quiz_id,
+                user_id : x.user_id,
+                attempt : x.attempt,
+                course_id,
+                assignment_id,
+                score : x.score,
+                points_possible,
+                finished_at : x.submitted_at,
+            })
+            .collect())
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, attempts : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, attempts).await
+    }
+
+    /// Removes attempts that are no longer part of the refreshed courses, and
+    /// everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], attempts : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM quiz_attempts AS att
+            WHERE NOT (att.course_id = ANY($1)) OR NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[], $4::INT[]) AS keep(quiz_id, user_id, attempt)
+                WHERE keep.quiz_id = att.quiz_id AND keep.user_id = att.user_id
+                    AND keep.attempt = att.attempt);
+        ")
+        .bind(course_ids)
+        .bind(attempts.iter().map(|x| x.quiz_id).collect::<Vec<i32>>())
+        .bind(attempts.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(attempts.iter().map(|x| x.attempt).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Quiz Attempt Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for QuizAttempt {
+    const TABLE : &'static str = "quiz_attempts";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO quiz_attempts
+            (quiz_id, user_id, attempt, course_id, assignment_id, score,
+             points_possible, finished_at)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::INT[], $4::INT[], $5::INT[],
+                                 $6::NUMERIC[], $7::NUMERIC[], $8::TIMESTAMPTZ[])
+            ON CONFLICT (quiz_id, user_id, attempt) DO UPDATE SET
+                course_id = EXCLUDED.course_id,
+                assignment_id = EXCLUDED.assignment_id,
+                score = EXCLUDED.score,
+                points_possible = EXCLUDED.points_possible,
+                finished_at = EXCLUDED.finished_at;
+        ")
+        .bind(rows.iter().map(|x| x.quiz_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.attempt).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.assignment_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.score).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.points_possible).collect::<Vec<Decimal>>())
+        .bind(rows.iter().map(|x| x.finished_at).collect::<Vec<Option<DateTime<Utc>>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Quiz Attempt SQL Failure", e))?;
+        Ok(())
+    }
+}
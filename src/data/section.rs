@@ -0,0 +1,90 @@
+use sqlx::{Postgres, Transaction};
+use serde::Deserialize;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
+use crate::macros::err;
+
+#[derive(Deserialize)]
+struct Section {
+    id : i32,
+    name : String,
+    students : Option<Vec<SectionUser>>,
+}
+
+#[derive(Deserialize)]
+struct SectionUser {
+    id : i32,
+}
+
+/// A student's enrollment in one section of a course.
+pub struct SectionStudent {
+    pub course_id : i32,
+    pub id : i32,
+    pub name : String,
+    pub user_id : i32,
+}
+
+impl SectionStudent {
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>, String> {
+        let sections = client.json_api_get::<Section>(&format!(
+            "/api/v1/courses/{}/sections?include[]=students", course_id))
+            .await?;
+        Ok(sections.into_iter()
+            .flat_map(|x| {
+                let (id, name) = (x.id, x.name);
+                x.students.unwrap_or_default().into_iter()
+                    .map(move |s| SectionStudent { course_id, id, name : name.clone(), user_id : s.id })
+            })
+            .collect())
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, students : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, students).await
+    }
+
+    /// Removes enrollments no longer in a section of the refreshed courses,
+    /// and everything for courses that are no longer configured.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], students : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM curr_sections AS sec
+            WHERE NOT (sec.course_id = ANY($1)) OR NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[], $4::INT[]) AS keep(course_id, id, user_id)
+                WHERE keep.course_id = sec.course_id AND keep.id = sec.id
+                    AND keep.user_id = sec.user_id);
+        ")
+        .bind(course_ids)
+        .bind(students.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(students.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(students.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Section Table Cleanup Failure",e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkInsert for SectionStudent {
+    const TABLE : &'static str = "curr_sections";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_sections
+            (course_id, id, name, user_id)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::INT[])
+            ON CONFLICT (course_id, id, user_id) DO UPDATE SET
+                name = EXCLUDED.name;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Section SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Pool, Postgres, Transaction};
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::student::Student;
+use crate::macros::err;
+
+/// A student's current score and grade as of one refresh, None while
+/// nothing has been graded.
+pub struct ScoreSnapshot {
+    pub course_id : i32,
+    pub user_id : i32,
+    pub taken_at : DateTime<Utc>,
+    pub curr_grade : Option<String>,
+    pub curr_score : Option<Decimal>,
+}
+
+/// The last snapshot of a day for one student.
+#[derive(sqlx::FromRow)]
+pub struct DailyScore {
+    pub user_id : i32,
+    pub name : String,
+    pub day : NaiveDate,
+    pub curr_score : Decimal,
+}
+
+impl ScoreSnapshot {
+    pub fn from_student(student : &Student, taken_at : DateTime<Utc>) -> Self {
+        let (curr_grade, curr_score) = student.current_grade();
+        Self { course_id : student.course_id, user_id : student.id, taken_at, curr_grade, curr_score }
+    }
+
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, snapshots : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, snapshots).await
+    }
+
+    /// One score per day for each student still enrolled in the course, the
+    /// last one taken that day, oldest first.  Snapshots taken before the
+    /// student had a score are left out.
+    pub async fn daily(database : &Pool<Postgres>, course_id : i32) -> Result<Vec<DailyScore>, String> {
+        sqlx::query_as::<_,DailyScore>(
+                "
+                SELECT DISTINCT ON (snap.user_id, snap.taken_at::DATE)
+                    snap.user_id, stu.name, snap.taken_at::DATE AS day,
+                    snap.curr_score
+                FROM score_snapshots AS snap
+                INNER JOIN curr_students AS stu
+                    ON stu.course_id = snap.course_id AND stu.id = snap.user_id
+                WHERE snap.course_id = $1 AND snap.curr_score IS NOT NULL
+                ORDER BY snap.user_id, snap.taken_at::DATE, snap.taken_at DESC;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Score Snapshot SQL Query Failure",e))
+    }
+}
+
+#[async_trait]
+impl BulkInsert for ScoreSnapshot {
+    const TABLE : &'static str = "score_snapshots";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO score_snapshots
+            (course_id, user_id, taken_at, curr_grade, curr_score)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TIMESTAMPTZ[], $4::TEXT[], $5::NUMERIC[])
+            ON CONFLICT (course_id, user_id, taken_at) DO NOTHING;
+        ")
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.taken_at).collect::<Vec<DateTime<Utc>>>())
+        .bind(rows.iter().map(|x| x.curr_grade.clone()).collect::<Vec<Option<String>>>())
+        .bind(rows.iter().map(|x| x.curr_score).collect::<Vec<Option<Decimal>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Score Snapshot SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
-use sqlx::{Pool, Postgres};
+use sqlx::{Postgres, Transaction};
 use serde::Deserialize;
-use crate::data::connections::{json_api_get, json_api_get_single};
-use crate::data::config::Config;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
 use crate::macros::err;
 
 #[derive(Deserialize)]
 pub struct Student {
     pub id : i32,
+    // Not part of the Canvas user, filled in by fetch
+    #[serde(default)]
+    pub course_id : i32,
     pub name : String,
     pub enrollments : Option<Vec<Enrollment>>,
 }
@@ -19,12 +24,16 @@ pub struct Enrollment {
 #[derive(Deserialize)]
 pub struct Grades {
     pub current_grade : Option<String>,
-    pub current_score : Option<f32>
+    pub current_score : Option<Decimal>,
+    pub final_grade : Option<String>,
+    pub final_score : Option<Decimal>,
 }
 
 impl Student {
-    pub async fn load(database : &Pool<Postgres>, config : &Config, course_id : i32) -> Result<(),String> {
-        let students = json_api_get::<Student>(config,&format!(
+    /// Downloads the students enrolled in a course, leaving out the course's
+    /// test student.
+    pub async fn fetch(client : &CanvasClient, course_id : i32) -> Result<Vec<Self>,String> {
+        let mut students = client.json_api_get::<Student>(&format!(
                             "/api/v1/courses/{}/users\
                             ?enrollment_type[]=student\
                             &include[]=total_scores\
@@ -33,63 +42,84 @@ impl Student {
                             &enrollment_state[]=invited\
                             &enrollment_state[]=completed", course_id))
                             .await?;
-        let test_student = json_api_get_single::<Student>(config,&format!(
+        let test_student = client.json_api_get_single::<Student>(&format!(
             "/api/v1/courses/{}/student_view_student\
             ?", course_id))
             .await?;
-        for s in students.iter() {
-            if s.id == test_student.id {
-                continue;
-            }
-            let mut grade = String::new();
-            let mut score = 0.0;
-            if let Some(enrollments) = &s.enrollments {
-                if let Some(enrollment) = enrollments.first() {                   grade = enrollment.grades.current_grade.clone().unwrap_or(String::new());
-                    score = enrollment.grades.current_score.unwrap_or(0.0);
-                }
-            }
-            sqlx::query(
-                "
-                INSERT INTO curr_students 
-                (id, course_id, name, curr_grade, curr_score)
-                    VALUES
-                ($1, $2, $3, $4, $5);
-            ")
-            .bind(s.id)
-            .bind(course_id)
-            .bind(s.name.clone())
-            .bind(grade)
-            .bind(score)
-            .execute(database)
-            .await
-            .map_err(|e| err!("Student SQL Failure", e))?;
-        }
-        Ok(())
+        students.retain(|x| x.id != test_student.id);
+        students.iter_mut().for_each(|x| x.course_id = course_id);
+        Ok(students)
     }
 
-    pub async fn create_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query(
-         "
-             CREATE TABLE curr_students(
-                 id INT,
-                 course_id INT,
-                 name TEXT,
-                 curr_grade TEXT,
-                 curr_score REAL
-             );
-         ")
-         .execute(database)
-         .await
-         .map_err(|e| err!("SQL Student Table Creation Failure",e))?;
-         Ok(())
-     }
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, students : &[Self]) -> Result<BulkStats,String> {
+        bulk_insert(tx, students).await
+    }
+
+    /// Current grade and score, where Canvas leaves out ungraded work.  Both
+    /// are None until something has been graded.
+    pub fn current_grade(&self) -> (Option<String>, Option<Decimal>) {
+        self.enrollments.as_ref()
+            .and_then(|x| x.first())
+            .map(|x| (x.grades.current_grade.clone(), x.grades.current_score))
+            .unwrap_or((None, None))
+    }
+
+    /// Final grade and score, where Canvas counts ungraded work as zero.
+    pub fn final_grade(&self) -> (Option<String>, Option<Decimal>) {
+        self.enrollments.as_ref()
+            .and_then(|x| x.first())
+            .map(|x| (x.grades.final_grade.clone(), x.grades.final_score))
+            .unwrap_or((None, None))
+    }
 
-     pub async fn drop_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query("DROP TABLE IF EXISTS curr_students;")
-            .execute(database)
-            .await
-            .map_err(|e| err!("SQL Student Table Drop Failure",e))?;
+    /// Removes enrollments in the refreshed courses that Canvas no longer
+    /// returns, such as dropped students.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], students : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            DELETE FROM curr_students AS stu
+            WHERE stu.course_id = ANY($1) AND NOT EXISTS (
+                SELECT 1 FROM UNNEST($2::INT[], $3::INT[]) AS keep(course_id, id)
+                WHERE keep.course_id = stu.course_id AND keep.id = stu.id);
+        ")
+        .bind(course_ids)
+        .bind(students.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(students.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Student Table Cleanup Failure",e))?;
         Ok(())
     }
 
 }
+
+#[async_trait]
+impl BulkInsert for Student {
+    const TABLE : &'static str = "curr_students";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        let (grades, scores) : (Vec<String>, Vec<Option<Decimal>>) = rows.iter()
+            .map(|x| x.current_grade())
+            .map(|(grade, score)| (grade.unwrap_or_default(), score))
+            .unzip();
+        sqlx::query(
+            "
+            INSERT INTO curr_students 
+            (id, course_id, name, curr_grade, curr_score)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::TEXT[], $4::TEXT[], $5::NUMERIC[])
+            ON CONFLICT (course_id, id) DO UPDATE SET
+                name = EXCLUDED.name,
+                curr_grade = EXCLUDED.curr_grade,
+                curr_score = EXCLUDED.curr_score;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.course_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.name.clone()).collect::<Vec<String>>())
+        .bind(grades)
+        .bind(scores)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Student SQL Failure", e))?;
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
-use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{Postgres, Transaction};
 use serde::Deserialize;
-use crate::data::connections::json_api_get;
-use crate::data::config::Config;
+use rust_decimal::Decimal;
+use async_trait::async_trait;
+use crate::data::bulk::{bulk_insert, BulkInsert, BulkStats};
+use crate::data::connections::CanvasClient;
 use crate::macros::err;
 
 #[derive(Deserialize, Debug)]
@@ -9,73 +13,115 @@ pub struct Submission {
     pub id : i32,
     pub assignment_id : i32,
     pub user_id : i32,
-    pub score : Option<f32>,
+    pub score : Option<Decimal>,
     pub excused : Option<bool>,
     pub missing : Option<bool>,
     pub late : Option<bool>,
     pub attempt : Option<i32>,
     pub grade_matches_current_submission : Option<bool>,
+    pub submitted_at : Option<DateTime<Utc>>,
 }
 
 impl Submission {
-    pub async fn load(database : &Pool<Postgres>, config : &Config, course : i32) -> Result<(), String> {
-        let submissions = json_api_get::<Submission>(config,&format!(
-                            "/api/v1/courses/{}/students/submissions\
-                            ?student_ids[]=all\
-                            &enrollment_state=active", course))
-                            .await?;        
-        for s in submissions.iter() {
-            sqlx::query(
-                "
-                INSERT INTO curr_submissions 
-                (id, assignment_id, user_id, score, excused,
-                 missing, late, attempt, current_submission)
-                 VALUES
-                ($1, $2, $3, $4, $5, $6, $7, $8, $9);
-            ")
-            .bind(s.id)
-            .bind(s.assignment_id)
-            .bind(s.user_id)
-            .bind(s.score)
-            .bind(s.excused.unwrap_or(false))
-            .bind(s.missing.unwrap_or(false))
-            .bind(s.late.unwrap_or(false))
-            .bind(s.attempt.unwrap_or(0))
-            .bind(s.grade_matches_current_submission.unwrap_or(false))
-            .execute(database)
+    /// Downloads the submissions for a course.  With `since` only submissions
+    /// submitted or graded after that time are requested; otherwise every
+    /// submission is downloaded.
+    pub async fn fetch(client : &CanvasClient, course : i32,
+                       since : Option<DateTime<Utc>>) -> Result<Vec<Self>, String> {
+        let base_url = format!(
+            "/api/v1/courses/{}/students/submissions\
+            ?student_ids[]=all\
+            &enrollment_state=active", course);
+        let submissions = match since {
+            Some(since) => {
+                let since = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+                let mut changed = HashMap::<i32, Submission>::new();
+                for filter in ["submitted_since", "graded_since"] {
+                    let results = client.json_api_get::<Submission>(
+                        &format!("{}&{}={}", base_url, filter, since))
+                        .await?;
+                    changed.extend(results.into_iter().map(|x| (x.id, x)));
+                }
+                changed.into_values().collect::<Vec<Submission>>()
+            }
+            None => client.json_api_get::<Submission>(&base_url).await?
+        };
+        Ok(submissions)
+    }
+
+    /// Downloads every submission of a concluded course, whose students no
+    /// longer have an active enrollment.
+    pub async fn fetch_concluded(client : &CanvasClient, course : i32) -> Result<Vec<Self>, String> {
+        client.json_api_get::<Submission>(&format!(
+            "/api/v1/courses/{}/students/submissions\
+            ?student_ids[]=all\
+            &enrollment_state=concluded", course))
             .await
-            .map_err(|e| err!("Submission SQL Failure", e))?;
-        }
-        Ok(())
+    }
+
+    /// Inserts submissions, replacing any existing row with the same id.
+    pub async fn store(tx : &mut Transaction<'_, Postgres>, submissions : &[Self]) -> Result<BulkStats, String> {
+        bulk_insert(tx, submissions).await
     }
 
     
-    pub async fn create_table(database : &Pool<Postgres>) -> Result<(), String> {
+    /// Removes submissions of the refreshed courses that were not part of a
+    /// full download.  Only valid after a full (not incremental) fetch.
+    pub async fn retain(tx : &mut Transaction<'_, Postgres>, course_ids : &[i32], submissions : &[Self]) -> Result<(), String> {
         sqlx::query(
-         "
-             CREATE TABLE curr_submissions(
-                 id INT,
-                 assignment_id INT,
-                 user_id INT,
-                 score REAL,
-                 excused BOOL,
-                 missing BOOL,
-                 late BOOL,
-                 attempt INT,
-                 current_submission BOOL);
-             ")
-         .execute(database)
-         .await
-         .map_err(|e| err!("SQL Submission Table Creation Failure",e))?;
-         Ok(())
+            "
+            DELETE FROM curr_submissions AS sub
+            USING curr_assignments AS asn
+            WHERE asn.id = sub.assignment_id
+                AND asn.course_id = ANY($1)
+                AND NOT (sub.id = ANY($2));
+        ")
+        .bind(course_ids)
+        .bind(submissions.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("SQL Submission Table Cleanup Failure",e))?;
+        Ok(())
     }
 
-    pub async fn drop_table(database : &Pool<Postgres>) -> Result<(), String> {
-        sqlx::query("DROP TABLE IF EXISTS curr_submissions;")
-            .execute(database)
-            .await
-            .map_err(|e| err!("SQL Submission Table Drop Failure",e))?;
+}
+
+#[async_trait]
+impl BulkInsert for Submission {
+    const TABLE : &'static str = "curr_submissions";
+
+    async fn insert_chunk(tx : &mut Transaction<'_, Postgres>, rows : &[Self]) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_submissions 
+            (id, assignment_id, user_id, score, excused,
+             missing, late, attempt, current_submission, submitted_at)
+            SELECT * FROM UNNEST($1::INT[], $2::INT[], $3::INT[], $4::NUMERIC[], $5::BOOL[],
+                                 $6::BOOL[], $7::BOOL[], $8::INT[], $9::BOOL[], $10::TIMESTAMPTZ[])
+            ON CONFLICT (id) DO UPDATE SET
+                assignment_id = EXCLUDED.assignment_id,
+                user_id = EXCLUDED.user_id,
+                score = EXCLUDED.score,
+                excused = EXCLUDED.excused,
+                missing = EXCLUDED.missing,
+                late = EXCLUDED.late,
+                attempt = EXCLUDED.attempt,
+                current_submission = EXCLUDED.current_submission,
+                submitted_at = EXCLUDED.submitted_at;
+        ")
+        .bind(rows.iter().map(|x| x.id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.assignment_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.user_id).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.score).collect::<Vec<Option<Decimal>>>())
+        .bind(rows.iter().map(|x| x.excused.unwrap_or(false)).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.missing.unwrap_or(false)).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.late.unwrap_or(false)).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.attempt.unwrap_or(0)).collect::<Vec<i32>>())
+        .bind(rows.iter().map(|x| x.grade_matches_current_submission.unwrap_or(false)).collect::<Vec<bool>>())
+        .bind(rows.iter().map(|x| x.submitted_at).collect::<Vec<Option<DateTime<Utc>>>>())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Submission SQL Failure", e))?;
         Ok(())
     }
-
-}
\ No newline at end of file
+}
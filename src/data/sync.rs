@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, Transaction};
+use crate::macros::err;
+
+/// Tracks when the submissions for each course were last downloaded so that
+/// an incremental refresh only asks Canvas for what changed since then.
+#[derive(sqlx::FromRow)]
+pub struct CourseSync {
+    pub course_id : i32,
+    pub submissions_synced : DateTime<Utc>,
+}
+
+impl CourseSync {
+    pub async fn load_all(database : &Pool<Postgres>) -> Result<HashMap<i32, DateTime<Utc>>, String> {
+        let results = sqlx::query_as::<_,CourseSync>(
+                "
+                SELECT course_id, submissions_synced
+                FROM curr_sync;
+            ")
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Sync SQL Query Failure",e))?;
+        Ok(results.into_iter()
+            .map(|x| (x.course_id, x.submissions_synced))
+            .collect())
+    }
+
+    pub async fn update(tx : &mut Transaction<'_, Postgres>, course_id : i32, synced : DateTime<Utc>) -> Result<(), String> {
+        sqlx::query(
+            "
+            INSERT INTO curr_sync
+            (course_id, submissions_synced)
+             VALUES
+            ($1, $2)
+            ON CONFLICT (course_id)
+            DO UPDATE SET submissions_synced = EXCLUDED.submissions_synced;
+        ")
+        .bind(course_id)
+        .bind(synced)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| err!("Sync SQL Failure", e))?;
+        Ok(())
+    }
+
+    pub async fn clear_table(tx : &mut Transaction<'_, Postgres>) -> Result<(), String> {
+        sqlx::query("DELETE FROM curr_sync;")
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| err!("SQL Sync Table Clear Failure",e))?;
+        Ok(())
+    }
+}
@@ -3,3 +3,4 @@ pub mod modules;
 pub mod data;
 pub mod manager;
 pub mod macros;
+pub mod mock;
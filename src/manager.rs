@@ -1,9 +1,16 @@
 use std::collections::HashMap;
+use std::env;
 use crate::data::config::Config;
 use crate::shell::Shell;
 use crate::modules::module::{ModuleTrait, ModuleType};
 use crate::modules::current_mod::CurrentMod;
-use crate::data::connections::connect_database;
+use crate::modules::resubmit_mod::ResubmitMod;
+use crate::modules::quiz_mod::QuizMod;
+use crate::modules::attendance_mod::AttendanceMod;
+use crate::modules::historical_mod::HistoricalMod;
+use crate::modules::outcomes_mod::OutcomesMod;
+use crate::data::connections::{connect_database, run_migrations, CanvasClient};
+use crate::data::fixtures::ApiMode;
 
 
 pub async fn run() {
@@ -20,8 +27,35 @@ pub async fn run() {
         Err(e) => { println!("{}",e); return; }
     };
 
+    if let Err(e) = run_migrations(&database).await {
+        println!("{}",e);
+        return;
+    }
+
+    let args = env::args().collect::<Vec<String>>();
+    let mode = match ApiMode::from_config(&config)
+        .and_then(|mode| ApiMode::from_args(&args, mode)) {
+        Ok(mode) => mode,
+        Err(e) => { println!("{}",e); return; }
+    };
+    match &mode {
+        ApiMode::Live => (),
+        ApiMode::Record(dir) => println!("Recording Canvas API traffic to {}", dir.display()),
+        ApiMode::Replay(dir) => println!("Replaying Canvas API traffic from {}", dir.display()),
+    }
+
+    let client = match CanvasClient::new(&config, mode) {
+        Ok(client) => client,
+        Err(e) => { println!("{}",e); return; }
+    };
+
     let mut modules = HashMap::<ModuleType, Box<dyn ModuleTrait>>::new();
-    modules.insert(ModuleType::Current, Box::new(CurrentMod::new(config.clone(), database.clone())));
+    modules.insert(ModuleType::Current, Box::new(CurrentMod::new(config.clone(), database.clone(), client.clone())));
+    modules.insert(ModuleType::Resubmit, Box::new(ResubmitMod::new(config.clone(), database.clone(), client.clone())));
+    modules.insert(ModuleType::Quiz, Box::new(QuizMod::new(config.clone(), database.clone(), client.clone())));
+    modules.insert(ModuleType::Attendance, Box::new(AttendanceMod::new(config.clone(), database.clone())));
+    modules.insert(ModuleType::Historical, Box::new(HistoricalMod::new(config.clone(), database.clone(), client.clone())));
+    modules.insert(ModuleType::Outcomes, Box::new(OutcomesMod::new(config.clone(), database.clone(), client.clone())));
     
     let mut shell = match Shell::new(modules).await {
         Ok(shell) => shell,
@@ -0,0 +1,519 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use reqwest::Url;
+use crate::mock::canvas::{MockCanvas, MockCourse, MockSubmission, OUTCOME_MASTERY, OUTCOME_POINTS};
+
+// Canvas defaults to 10 per page and caps requests at 100
+const DEFAULT_PER_PAGE : usize = 10;
+const MAX_PER_PAGE : usize = 100;
+
+type Params = Query<Vec<(String, String)>>;
+
+/// Routes for the Canvas endpoints horizons uses.
+pub fn router(canvas : Arc<MockCanvas>) -> Router {
+    Router::new()
+        .route("/api/v1/courses", get(courses))
+        .route("/api/v1/courses/:id", get(course))
+        .route("/api/v1/courses/:id/users", get(users))
+        .route("/api/v1/courses/:id/student_view_student", get(student_view_student))
+        .route("/api/v1/courses/:id/grading_standards/:standard_id", get(grading_standard))
+        .route("/api/v1/courses/:id/assignment_groups", get(assignment_groups))
+        .route("/api/v1/courses/:id/assignments", get(assignments))
+        .route("/api/v1/courses/:id/students/submissions", get(submissions))
+        .route("/api/v1/courses/:id/assignments/:assignment_id/submissions", get(assignment_submissions))
+        .route("/api/v1/courses/:id/sections", get(sections))
+        .route("/api/v1/courses/:id/outcome_group_links", get(outcome_group_links))
+        .route("/api/v1/courses/:id/rubrics", get(rubrics))
+        .route("/api/v1/courses/:id/outcome_results", get(outcome_results))
+        .with_state(canvas)
+}
+
+async fn courses(State(canvas) : State<Arc<MockCanvas>>,
+                 headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let courses = canvas.courses.iter()
+        .map(course_json)
+        .collect::<Vec<Value>>();
+    paginate(courses, &headers, &uri, &params, false, None)
+}
+
+async fn course(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    Json(course_json(course)).into_response()
+}
+
+fn course_json(course : &MockCourse) -> Value {
+    json!({
+        "id" : course.id,
+        "name" : course.code,
+        "course_code" : course.code,
+        "concluded" : course.concluded,
+        "term" : { "name" : course.term },
+        "total_students" : course.students.len(),
+        "apply_assignment_group_weights" : true,
+        "grading_standard_id" : course.grading_standard.as_ref().map(|x| x.id),
+    })
+}
+
+async fn grading_standard(State(canvas) : State<Arc<MockCanvas>>,
+                          Path((id, standard_id)) : Path<(i32, i32)>) -> Response {
+    let Some(standard) = canvas.course(id)
+        .and_then(|x| x.grading_standard.as_ref())
+        .filter(|x| x.id == standard_id) else {
+        return not_found();
+    };
+    Json(json!({
+        "id" : standard.id,
+        "title" : standard.title,
+        "context_type" : "Course",
+        "context_id" : id,
+        "grading_scheme" : standard.scheme.iter()
+            .map(|x| json!({ "name" : x.0, "value" : x.1 }))
+            .collect::<Vec<Value>>(),
+    })).into_response()
+}
+
+async fn users(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+               headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let mut users = course.students.iter()
+        .map(|x| {
+            let (score, grade) = course.current_grade(x.id);
+            let (final_score, final_grade) = course.final_grade(x.id);
+            json!({
+                "id" : x.id,
+                "name" : x.name,
+                "sortable_name" : x.name,
+                "enrollments" : [{
+                    "course_id" : course.id,
+                    "type" : "StudentEnrollment",
+                    "enrollment_state" : if course.concluded { "completed" } else { "active" },
+                    "grades" : {
+                        "current_score" : score,
+                        "current_grade" : grade,
+                        "final_score" : final_score,
+                        "final_grade" : final_grade,
+                    }
+                }]
+            })
+        })
+        .collect::<Vec<Value>>();
+    // Canvas includes the test student when asked for every enrollment state
+    users.push(json!({ "id" : course.test_student_id, "name" : "Test Student", "enrollments" : [] }));
+    paginate(users, &headers, &uri, &params, false, None)
+}
+
+async fn student_view_student(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    Json(json!({ "id" : course.test_student_id, "name" : "Test Student" })).into_response()
+}
+
+async fn assignment_groups(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                           headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let groups = course.groups.iter()
+        .map(|x| {
+            let mut rules = json!({});
+            if x.drop_lowest > 0 {
+                rules = json!({ "drop_lowest" : x.drop_lowest });
+            }
+            json!({
+                "id" : x.id,
+                "name" : x.name,
+                "position" : x.position,
+                "group_weight" : x.weight,
+                "rules" : rules,
+            })
+        })
+        .collect::<Vec<Value>>();
+    paginate(groups, &headers, &uri, &params, false, None)
+}
+
+async fn assignments(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                     headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let assignments = course.assignments.iter()
+        .map(|x| json!({
+            "id" : x.id,
+            "course_id" : course.id,
+            "name" : x.name,
+            "points_possible" : x.points_possible,
+            "assignment_group_id" : x.assignment_group_id,
+            "quiz_id" : x.quiz_id,
+            "omit_from_final_grade" : false,
+            "due_at" : timestamp(Some(x.due_at)),
+            "published" : true,
+        }))
+        .collect::<Vec<Value>>();
+    paginate(assignments, &headers, &uri, &params, false, None)
+}
+
+async fn submissions(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                     headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let since = |name : &str| params.iter()
+        .find(|(k, _)| k == name)
+        .and_then(|(_, v)| DateTime::parse_from_rfc3339(v).ok())
+        .map(|x| x.with_timezone(&Utc));
+    let submitted_since = since("submitted_since");
+    let graded_since = since("graded_since");
+
+    let history = params.iter().any(|(k, v)| k == "include[]" && v == "submission_history");
+
+    let submissions = course.submissions.iter()
+        .filter(|x| match submitted_since {
+            Some(since) => x.submitted_at.is_some_and(|t| t > since),
+            None => true
+        })
+        .filter(|x| match graded_since {
+            Some(since) => x.graded_at.is_some_and(|t| t > since),
+            None => true
+        })
+        .map(|x| submission_json(course, x, history))
+        .collect::<Vec<Value>>();
+    // Canvas paginates this endpoint with opaque bookmarks
+    paginate(submissions, &headers, &uri, &params, true, None)
+}
+
+async fn assignment_submissions(State(canvas) : State<Arc<MockCanvas>>, Path((id, assignment_id)) : Path<(i32, i32)>,
+                                headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    if !course.assignments.iter().any(|x| x.id == assignment_id) {
+        return not_found();
+    }
+    let history = params.iter().any(|(k, v)| k == "include[]" && v == "submission_history");
+    let submissions = course.submissions.iter()
+        .filter(|x| x.assignment_id == assignment_id)
+        .map(|x| submission_json(course, x, history))
+        .collect::<Vec<Value>>();
+    paginate(submissions, &headers, &uri, &params, false, None)
+}
+
+fn submission_json(course : &MockCourse, x : &MockSubmission, history : bool) -> Value {
+    let mut submission = json!({
+        "id" : x.id,
+        "assignment_id" : x.assignment_id,
+        "user_id" : x.user_id,
+        "score" : x.score,
+        "excused" : x.excused,
+        "missing" : x.missing,
+        "late" : x.late,
+        "attempt" : x.attempt,
+        "grade_matches_current_submission" : x.grade_matches_current_submission,
+        "submitted_at" : timestamp(x.submitted_at),
+        "graded_at" : timestamp(x.graded_at),
+        "workflow_state" : submission_state(x.score, x.submitted_at),
+    });
+    if history {
+        let points_possible = course.assignments.iter()
+            .find(|a| a.id == x.assignment_id)
+            .map(|a| a.points_possible)
+            .unwrap_or(0.0);
+        submission["submission_history"] = x.history(points_possible).into_iter()
+            .map(|v| json!({
+                "attempt" : v.attempt,
+                "score" : v.score,
+                "submitted_at" : timestamp(v.submitted_at),
+                "grade_matches_current_submission" : v.grade_matches_current_submission,
+            }))
+            .collect::<Value>();
+    }
+    submission
+}
+
+async fn sections(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                  headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let sections = course.sections.iter()
+        .map(|x| json!({
+            "id" : x.id,
+            "course_id" : course.id,
+            "name" : x.name,
+            "students" : x.student_ids.iter()
+                .filter_map(|s| course.students.iter().find(|y| y.id == *s))
+                .map(|s| json!({ "id" : s.id, "name" : s.name }))
+                .collect::<Vec<Value>>(),
+        }))
+        .collect::<Vec<Value>>();
+    paginate(sections, &headers, &uri, &params, false, None)
+}
+
+async fn outcome_group_links(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                             headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    let links = course.outcomes.iter()
+        .map(|x| json!({
+            "url" : format!("/api/v1/courses/{}/outcome_groups/{}/outcomes/{}", course.id, course.id, x.id),
+            "context_id" : course.id,
+            "context_type" : "Course",
+            "outcome_group" : { "id" : course.id, "title" : course.code },
+            "outcome" : {
+                "id" : x.id,
+                "title" : x.title,
+                "display_name" : x.title,
+                "description" : x.description,
+                "mastery_points" : OUTCOME_MASTERY,
+                "points_possible" : OUTCOME_POINTS,
+                "calculation_method" : x.calculation_method,
+                "calculation_int" : x.calculation_int,
+            }
+        }))
+        .collect::<Vec<Value>>();
+    paginate(links, &headers, &uri, &params, false, None)
+}
+
+async fn rubrics(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                 headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    // One rubric per group with outcomes, used by all of its assignments
+    let rubrics = course.groups.iter()
+        .filter(|g| course.outcomes.iter().any(|x| x.assignment_group_id == g.id))
+        .map(|g| json!({
+            "id" : g.id,
+            "title" : format!("{} Rubric", g.name),
+            "context_id" : course.id,
+            "context_type" : "Course",
+            "points_possible" : OUTCOME_POINTS * course.outcomes.iter().filter(|x| x.assignment_group_id == g.id).count() as f64,
+            "data" : course.outcomes.iter()
+                .filter(|x| x.assignment_group_id == g.id)
+                .map(|x| json!({
+                    "id" : format!("_{}", x.id),
+                    "description" : x.title,
+                    "points" : OUTCOME_POINTS,
+                    "learning_outcome_id" : x.id,
+                }))
+                .collect::<Vec<Value>>(),
+            "associations" : course.assignments.iter()
+                .filter(|x| x.assignment_group_id == g.id && x.points_possible > 0.0)
+                .map(|x| json!({
+                    "association_id" : x.id,
+                    "association_type" : "Assignment",
+                    "use_for_grading" : true,
+                }))
+                .collect::<Vec<Value>>(),
+        }))
+        .collect::<Vec<Value>>();
+    paginate(rubrics, &headers, &uri, &params, false, None)
+}
+
+async fn outcome_results(State(canvas) : State<Arc<MockCanvas>>, Path(id) : Path<i32>,
+                         headers : HeaderMap, uri : Uri, Query(params) : Params) -> Response {
+    let Some(course) = canvas.course(id) else {
+        return not_found();
+    };
+    // Canvas links results to users and outcomes by string ids
+    let results = course.outcome_results().into_iter()
+        .map(|x| json!({
+            "id" : x.id,
+            "score" : x.score,
+            "possible" : OUTCOME_POINTS,
+            "mastery" : x.score >= OUTCOME_MASTERY,
+            "submitted_or_assessed_at" : timestamp(x.assessed_at),
+            "links" : {
+                "user" : x.user_id.to_string(),
+                "learning_outcome" : x.outcome_id.to_string(),
+                "alignment" : format!("assignment_{}", x.assignment_id),
+            }
+        }))
+        .collect::<Vec<Value>>();
+    paginate(results, &headers, &uri, &params, false, Some("outcome_results"))
+}
+
+fn submission_state(score : Option<f64>, submitted_at : Option<DateTime<Utc>>) -> &'static str {
+    match (score, submitted_at) {
+        (Some(_), _) => "graded",
+        (None, Some(_)) => "submitted",
+        (None, None) => "unsubmitted",
+    }
+}
+
+fn timestamp(time : Option<DateTime<Utc>>) -> Option<String> {
+    time.map(|x| x.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({
+        "errors" : [{ "message" : "The specified resource does not exist." }]
+    }))).into_response()
+}
+
+/// Returns one page of `items` with a Canvas style Link header.  Numbered
+/// pages produce current/next/prev/first/last; bookmark pages (like Canvas
+/// uses for submissions) only current/next/first.  The next link is always
+/// listed last so clients must not rely on a trailing comma.  Some endpoints
+/// wrap the page in an object under `wrap`.
+fn paginate(items : Vec<Value>, headers : &HeaderMap, uri : &Uri, params : &[(String, String)],
+            bookmark : bool, wrap : Option<&str>) -> Response {
+    let per_page = params.iter()
+        .find(|(k, _)| k == "per_page")
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let page_param = params.iter()
+        .find(|(k, _)| k == "page")
+        .map(|(_, v)| v.clone());
+    let page = match page_param.as_deref().and_then(|x| x.strip_prefix("bookmark:")) {
+        Some(token) => usize::from_str_radix(token, 16).unwrap_or(0) / per_page,
+        None => page_param.and_then(|x| x.parse::<usize>().ok()).unwrap_or(1).max(1) - 1
+    };
+    let pages = items.len().div_ceil(per_page).max(1);
+
+    let host = headers.get("host")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("localhost");
+    let page_url = |index : usize| {
+        let Ok(mut url) = Url::parse(&format!("http://{}{}", host, uri.path())) else {
+            return String::new();
+        };
+        {
+            let mut query = url.query_pairs_mut();
+            for (k, v) in params.iter().filter(|(k, _)| k != "page" && k != "per_page") {
+                query.append_pair(k, v);
+            }
+            if bookmark {
+                query.append_pair("page", &format!("bookmark:{:x}", index * per_page));
+            }
+            else {
+                query.append_pair("page", &(index + 1).to_string());
+            }
+            query.append_pair("per_page", &per_page.to_string());
+        }
+        format!("<{}>", url)
+    };
+
+    let mut links = vec![format!("{}; rel=\"current\"", page_url(page))];
+    if !bookmark && page > 0 {
+        links.push(format!("{}; rel=\"prev\"", page_url(page - 1)));
+    }
+    links.push(format!("{}; rel=\"first\"", page_url(0)));
+    if !bookmark {
+        links.push(format!("{}; rel=\"last\"", page_url(pages - 1)));
+    }
+    if page + 1 < pages {
+        links.push(format!("{}; rel=\"next\"", page_url(page + 1)));
+    }
+
+    let body = items.into_iter()
+        .skip(page * per_page)
+        .take(per_page)
+        .collect::<Vec<Value>>();
+    let body = match wrap {
+        Some(key) => json!({ key : body }),
+        None => Value::Array(body)
+    };
+    let mut response = Json(body).into_response();
+    if let Ok(value) = HeaderValue::from_str(&links.join(",")) {
+        response.headers_mut().insert("link", value);
+    }
+    response.headers_mut().insert("x-request-cost", HeaderValue::from_static("0.5"));
+    response.headers_mut().insert("x-rate-limit-remaining", HeaderValue::from_static("700.0"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use rust_decimal::Decimal;
+    use tokio::net::TcpListener;
+    use crate::data::config::Config;
+    use crate::data::connections::CanvasClient;
+    use crate::data::fixtures::ApiMode;
+    use crate::data::quiz_attempt::QuizAttempt;
+    use crate::mock::canvas::MockOptions;
+    use super::*;
+
+    // Serves freshly generated mock data on a free port, returning a client
+    // pointed at it
+    async fn start() -> (Arc<MockCanvas>, CanvasClient) {
+        let canvas = Arc::new(MockCanvas::generate(&MockOptions::default()));
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = router(canvas.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let config : Config = toml::from_str(&format!(r#"
+            [general]
+            server = "http://127.0.0.1:{}"
+            token = "mock"
+            postgres = "unused"
+            postgres_pool = 1
+
+            [current_config]
+            exclude_zero_grades = false
+            courses = [["CSE110", 1001]]
+        "#, port)).unwrap();
+        (canvas, CanvasClient::new(&config, ApiMode::Live).unwrap())
+    }
+
+    #[tokio::test]
+    async fn numbered_pages_are_followed() {
+        let (canvas, client) = start().await;
+        let course = canvas.course(1001).unwrap();
+        let users = client.json_api_get::<Value>("/api/v1/courses/1001/users?per_page=7").await.unwrap();
+        // Canvas lists the course's test student with the real ones
+        let mut expected = course.students.iter().map(|x| x.id).collect::<Vec<i32>>();
+        expected.push(course.test_student_id);
+        assert_eq!(client.stats().requests as usize, expected.len().div_ceil(7));
+        assert_eq!(users.iter().map(|x| x["id"].as_i64().unwrap() as i32).collect::<Vec<i32>>(), expected);
+    }
+
+    #[tokio::test]
+    async fn bookmark_pages_are_followed() {
+        let (canvas, client) = start().await;
+        let course = canvas.course(1001).unwrap();
+        let submissions = client.json_api_get::<Value>(
+            "/api/v1/courses/1001/students/submissions?student_ids[]=all&per_page=50").await.unwrap();
+        assert!(client.stats().requests > 1);
+        let ids = submissions.iter()
+            .map(|x| x["id"].as_i64().unwrap() as i32)
+            .collect::<HashSet<i32>>();
+        assert_eq!(ids.len(), submissions.len());
+        assert_eq!(ids, course.submissions.iter().map(|x| x.id).collect::<HashSet<i32>>());
+    }
+
+    #[tokio::test]
+    async fn quiz_attempts_come_from_submission_history() {
+        let (canvas, client) = start().await;
+        let course = canvas.course(1001).unwrap();
+        let assignment = course.assignments.iter().find(|x| x.quiz_id.is_some()).unwrap();
+        let attempts = QuizAttempt::fetch(&client, 1001, assignment.quiz_id.unwrap(), assignment.id,
+            Decimal::from_f64_retain(assignment.points_possible).unwrap()).await.unwrap();
+
+        let taken = course.submissions.iter()
+            .filter(|x| x.assignment_id == assignment.id && x.attempt.is_some())
+            .collect::<Vec<_>>();
+        assert!(taken.iter().any(|x| x.attempt > Some(1)));
+        for submission in taken {
+            let mut numbers = attempts.iter()
+                .filter(|x| x.user_id == submission.user_id)
+                .map(|x| x.attempt)
+                .collect::<Vec<i32>>();
+            numbers.sort();
+            assert_eq!(numbers, (1..=submission.attempt.unwrap()).collect::<Vec<i32>>());
+        }
+    }
+}
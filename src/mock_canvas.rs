@@ -0,0 +1,75 @@
+use std::env;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use horizons::mock::canvas::{MockCanvas, MockOptions};
+use horizons::mock::server::router;
+
+const USAGE : &str = "\
+mock_canvas [options]
+    --port <port>            port to listen on (default 8080)
+    --seed <seed>            random seed for the synthetic data (default 42)
+    --courses <id,id,...>    course ids to serve (default 1001,1002)
+    --students <count>       students per course (default 30)
+    --assignments <count>    graded assignments per course (default 12)
+    --shared <count>         students also enrolled in the next course (default 3)
+    --past-terms <count>     concluded offerings of each course (default 2)";
+
+#[tokio::main]
+async fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let (port, options) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => { println!("{}\n\n{}", e, USAGE); return; }
+    };
+
+    let canvas = MockCanvas::generate(&options);
+    println!("Mock Canvas listening on http://localhost:{}", port);
+    println!("Point config.toml at it with:");
+    println!();
+    println!("[general]");
+    println!("server = \"http://localhost:{}\"", port);
+    println!();
+    println!("[current_config]");
+    print!("courses = [");
+    for (index, course) in canvas.courses.iter().filter(|x| !x.concluded).enumerate() {
+        if index > 0 {
+            print!(", ");
+        }
+        print!("[\"{}\", {}]", course.code.replace(' ', ""), course.id);
+    }
+    println!("]");
+    println!();
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => { println!("Unable to listen on port {}: {}", port, e); return; }
+    };
+    if let Err(e) = axum::serve(listener, router(Arc::new(canvas))).await {
+        println!("{}", e);
+    }
+}
+
+fn parse_args(args : &[String]) -> Result<(u16, MockOptions), String> {
+    let mut port = 8080;
+    let mut options = MockOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter.next()
+            .ok_or(format!("Missing value for {}", arg))?;
+        let invalid = |_| format!("Invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--port" => port = value.parse().map_err(invalid)?,
+            "--seed" => options.seed = value.parse().map_err(invalid)?,
+            "--students" => options.students = value.parse().map_err(invalid)?,
+            "--assignments" => options.assignments = value.parse().map_err(invalid)?,
+            "--shared" => options.shared_students = value.parse().map_err(invalid)?,
+            "--past-terms" => options.past_terms = value.parse().map_err(invalid)?,
+            "--courses" => options.course_ids = value.split(',')
+                .map(|x| x.trim().parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(invalid)?,
+            _ => return Err(format!("Unknown option {}", arg))
+        }
+    }
+    Ok((port, options))
+}
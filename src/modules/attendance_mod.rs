@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use crate::data::attendance::AttendanceRecord;
+use crate::data::config::Config;
+use crate::data::grading_scheme::GradingScheme;
+use crate::modules::module::{print_vec, ModuleTrait, ZeroMode};
+use crate::modules::statistics::correlation;
+use crate::macros::err;
+
+// Attendance counts for one student or one class meeting
+#[derive(Default)]
+struct Tally {
+    present : i64,
+    late : i64,
+    absent : i64,
+    excused : i64,
+}
+
+impl Tally {
+    fn add(&mut self, status : &str) {
+        match status {
+            "present" => self.present += 1,
+            "late" => self.late += 1,
+            "absent" => self.absent += 1,
+            _ => self.excused += 1
+        }
+    }
+
+    fn sessions(&self) -> i64 {
+        self.present + self.late + self.absent + self.excused
+    }
+
+    /// Percent attended, with late arrivals earning partial credit and
+    /// excused absences left out.  None when nothing counts.
+    fn rate(&self, late_credit : Decimal) -> Option<Decimal> {
+        let counted = self.present + self.late + self.absent;
+        if counted == 0 {
+            return None;
+        }
+        Some((Decimal::from(self.present) + Decimal::from(self.late) * late_credit)
+            / Decimal::from(counted) * Decimal::ONE_HUNDRED)
+    }
+}
+
+const COMMANDS : [&str; 3] = ["import", "students", "sessions"];
+
+pub struct AttendanceMod {
+    config : Config,
+    course_lookup : HashMap<String, i32>,
+    database : Pool<Postgres>,
+}
+
+#[async_trait]
+impl ModuleTrait for AttendanceMod {
+    fn get_name(&self) -> String {
+        "Attendance".to_string()
+    }
+
+    async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        let (flags, parsed) : (Vec<&str>, Vec<&str>) = parsed.into_iter()
+            .partition(|x| x.starts_with("--"));
+        if let Some(command) = parsed.first() {
+            if !COMMANDS.contains(command) {
+                return Ok(false);
+            }
+            let Some(zeros) = ZeroMode::from_flags(self.config.current_config.exclude_zero_grades, &flags) else {
+                return Ok(true);
+            };
+            return match *command {
+                "import" => {
+                    match (parsed.get(1), parsed.get(2)) {
+                        (Some(course), Some(file)) => {
+                            if let Some(course_id) = self.course_lookup.get(*course) {
+                                self.import(*course_id, Path::new(file)).await?;
+                            }
+                            else {
+                                println!("Invalid Course ID");
+                            }
+                        }
+                        (Some(_), None) => println!("Missing CSV File"),
+                        _ => println!("Missing Course ID")
+                    }
+                    Ok(true)
+                }
+                "students" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.students(*course_id, zeros).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                "sessions" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.sessions(*course_id).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    /// Nothing to download: the Roll Call assignment comes with the Current
+    /// module's refresh and CSV exports are brought in with import.
+    async fn refresh(&mut self, _full : bool) -> Result<(),String> {
+        println!("Loading Module: {}", self.get_name());
+        println!("Attendance uses the '{}' assignment from the Current module,", self.config.attendance_config.assignment);
+        println!("or a Roll Call CSV export loaded with import");
+        Ok(())
+    }
+
+    fn help(&self) {
+        println!("import <course id> <csv file>");
+        println!("students <course id> [--zeros|--no-zeros]");
+        println!("sessions <course id>");
+        print!("     <course id> =");
+        for course in &self.config.current_config.courses {
+            print!(" {}", course.0);
+        }
+        println!();
+    }
+
+}
+
+impl AttendanceMod {
+    pub fn new(config : Config, database : Pool<Postgres>) -> Self {
+        let mut course_lookup = HashMap::<String,i32>::new();
+        for course in config.current_config.courses.iter() {
+            course_lookup.insert(course.0.clone(), course.1);
+        }
+        Self { config, course_lookup, database }
+    }
+
+    async fn import(&self, course : i32, path : &Path) -> Result<(), String> {
+        // A bad file is a typo, not a reason to leave the shell
+        let records = match AttendanceRecord::from_csv(path, course) {
+            Ok(records) => records,
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
+        };
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Import Transaction Failure",e))?;
+        let stats = vec![AttendanceRecord::replace(&mut tx, course, &records).await?];
+        tx.commit()
+            .await
+            .map_err(|e| err!("Import Transaction Failure",e))?;
+        print_vec(&stats);
+        Ok(())
+    }
+
+    async fn records(&self, course : i32) -> Result<Vec<(i32, NaiveDate, String)>, String> {
+        sqlx::query_as::<_,(i32, NaiveDate, String)>(
+                "
+                SELECT user_id, class_date, status
+                FROM att_records
+                WHERE course_id = $1
+                ORDER BY class_date;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Attendance SQL Query Failure",e))
+    }
+
+    /// Bonus percentage points for an attendance rate.
+    fn bonus(&self, rate : Decimal) -> Decimal {
+        let settings = &self.config.attendance_config;
+        if rate < settings.bonus_threshold {
+            return Decimal::ZERO;
+        }
+        let span = Decimal::ONE_HUNDRED - settings.bonus_threshold;
+        if span <= Decimal::ZERO {
+            return settings.bonus_max;
+        }
+        (settings.bonus_max * (rate - settings.bonus_threshold) / span).min(settings.bonus_max)
+    }
+
+    /// With zeros excluded a zero on the Roll Call assignment counts as no
+    /// attendance taken rather than none attended.
+    async fn students(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            id : i32,
+            name : String,
+            curr_grade : String,
+            curr_score : Option<Decimal>,
+            roll_call : Option<Decimal>,
+        }
+        let settings = &self.config.attendance_config;
+        let students = sqlx::query_as::<_,Query>(
+                "
+                SELECT stu.id, stu.name, stu.curr_grade, stu.curr_score,
+                    sub.score / NULLIF(asn.points_possible, 0) * 100 AS roll_call
+                FROM curr_students AS stu
+                LEFT JOIN curr_assignments AS asn
+                    ON asn.course_id = stu.course_id AND asn.name = $2
+                LEFT JOIN curr_submissions AS sub
+                    ON sub.assignment_id = asn.id AND sub.user_id = stu.id
+                WHERE stu.course_id = $1
+                ORDER BY stu.curr_score DESC NULLS LAST, stu.name ASC;
+            ")
+            .bind(course)
+            .bind(&settings.assignment)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Attendance SQL Query Failure",e))?;
+        let records = self.records(course).await?;
+        let scheme = GradingScheme::for_course(&self.database, &self.config, course).await?;
+
+        // Imported records are more detailed, so they win over Roll Call
+        let from_csv = !records.is_empty();
+        let mut tallies = HashMap::<i32, Tally>::new();
+        for (user_id, _, status) in records.iter() {
+            tallies.entry(*user_id).or_default().add(status);
+        }
+        if from_csv {
+            println!("Source: CSV import ({} class meetings)",
+                records.iter().map(|x| x.1).collect::<std::collections::HashSet<_>>().len());
+        }
+        else {
+            println!("Source: '{}' assignment", settings.assignment);
+        }
+        println!("{}", zeros);
+        println!("Bonus: up to {} points, from {}% attendance (late = {}%)",
+            settings.bonus_max.normalize(), settings.bonus_threshold.normalize(),
+            (settings.late_credit * Decimal::ONE_HUNDRED).normalize());
+
+        println!("{:40} {:4} {:4} {:4} {:4} {:4} {:8} {:5} {:7} {:7} {:5} {:5}",
+            "NAME", "SESS", "PRES", "LATE", "ABS", "EXCU", "ATTEND-%", "BONUS", "SCORE-%", "BONUS-%", "GRADE", "NEW"
+        );
+        println!("{:-<40} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<8} {:-<5} {:-<7} {:-<7} {:-<5} {:-<5}",
+            "", "", "", "", "", "", "", "", "", "", "", ""
+        );
+        let mut pairs = Vec::<(Decimal, Decimal)>::new();
+        let mut changed = 0;
+        for student in students {
+            let tally = tallies.remove(&student.id).unwrap_or_default();
+            let rate = if from_csv {
+                tally.rate(settings.late_credit)
+            }
+            else {
+                student.roll_call.filter(|x| !(zeros.exclude && x.is_zero()))
+            };
+            let bonus = rate.map(|x| self.bonus(x)).unwrap_or_default();
+            let with_bonus = student.curr_score.map(|x| x + bonus);
+            let letter = with_bonus.map(|x| scheme.letter(x)).unwrap_or("-");
+            if let (Some(rate), Some(score)) = (rate, student.curr_score) {
+                pairs.push((rate, score));
+            }
+            if student.curr_score.is_some_and(|x| letter != scheme.letter(x)) {
+                changed += 1;
+            }
+            let percent = |x : Option<Decimal>| x.map(|y| format!("{:.2}%", y.round_dp(2))).unwrap_or("-".to_string());
+            let count = |x : i64| if from_csv { x.to_string() } else { "-".to_string() };
+            println!("{:40} {:>4} {:>4} {:>4} {:>4} {:>4} {:>8} {:5.2} {:>7} {:>7} {:5} {:5}",
+                student.name.chars().take(40).collect::<String>(),
+                count(tally.sessions()), count(tally.present), count(tally.late),
+                count(tally.absent), count(tally.excused),
+                percent(rate), bonus.round_dp(2), percent(student.curr_score), percent(with_bonus),
+                student.curr_grade, letter
+            );
+        }
+
+        println!();
+        println!("Correlation of attendance with current score (-1 to 1): {}",
+            correlation(&pairs)
+                .map(|x| format!("{:.2}", x.round_dp(2)))
+                .unwrap_or("n/a".to_string()));
+        println!("Students whose letter grade changes with the bonus: {}", changed);
+        Ok(())
+    }
+
+    async fn sessions(&self, course : i32) -> Result<(), String> {
+        let records = self.records(course).await?;
+        if records.is_empty() {
+            println!("No attendance imported, use import <course id> <csv file>");
+            return Ok(());
+        }
+        let late_credit = self.config.attendance_config.late_credit;
+        let mut sessions = BTreeMap::<NaiveDate, Tally>::new();
+        for (_, class_date, status) in records.iter() {
+            sessions.entry(*class_date).or_default().add(status);
+        }
+
+        println!("{:10} {:4} {:4} {:4} {:4} {:8}",
+            "DATE", "PRES", "LATE", "ABS", "EXCU", "ATTEND-%");
+        println!("{:-<10} {:-<4} {:-<4} {:-<4} {:-<4} {:-<8}",
+            "", "", "", "", "", "");
+        for (class_date, tally) in sessions.iter() {
+            println!("{:10} {:4} {:4} {:4} {:4} {:7.2}%",
+                class_date.format("%Y-%m-%d").to_string(),
+                tally.present, tally.late, tally.absent, tally.excused,
+                tally.rate(late_credit).unwrap_or_default().round_dp(2));
+        }
+        Ok(())
+    }
+}
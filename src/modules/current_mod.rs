@@ -1,19 +1,49 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::task::JoinHandle;
 use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
 use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use crate::data::assignment::Assignment;
+use crate::data::assignment_group::AssignmentGroup;
+use crate::data::bulk::{unique_rows, BulkStats};
+use crate::data::grading_scheme::{GradeCutoff, GradingScheme};
+use crate::data::snapshot::ScoreSnapshot;
 use crate::data::submission::Submission;
-use crate::modules::module::ModuleTrait;
+use crate::modules::grade_engine::{AssignmentDef, GradeOptions, Gradebook, StudentDef, SubmissionDef};
+use crate::modules::module::{find_by_name, group_header, print_vec, sparkline, ModuleTrait, ZeroMode};
+use crate::modules::statistics::Summary;
 use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
 use crate::data::course::Course;
+use crate::data::fixtures::ApiMode;
 use crate::data::student::Student;
+use crate::data::sync::CourseSync;
 use crate::macros::err;
 
+// Everything downloaded for one course during a refresh
+struct CourseData {
+    course_id : i32,
+    started : DateTime<Utc>,
+    students : Vec<Student>,
+    groups : Vec<AssignmentGroup>,
+    assignments : Vec<Assignment>,
+    submissions : Vec<Submission>,
+    cutoffs : Vec<GradeCutoff>,
+}
+
+// Daily scores shown in a sparkline
+const TREND_DAYS : usize = 60;
+
+const COMMANDS : [&str; 10] = ["courses", "students", "student", "grades", "assignment", "verify", "atrisk",
+                              "project", "whatif", "trend"];
+
 pub struct CurrentMod {
     config : Config,
     course_lookup : HashMap<String, i32>,
     database : Pool<Postgres>,
+    client : CanvasClient,
 }
 
 #[async_trait]
@@ -23,7 +53,15 @@ impl ModuleTrait for CurrentMod {
     }
 
     async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        let (flags, parsed) : (Vec<&str>, Vec<&str>) = parsed.into_iter()
+            .partition(|x| x.starts_with("--"));
         if let Some(command) = parsed.first() {
+            if !COMMANDS.contains(command) {
+                return Ok(false);
+            }
+            let Some(zeros) = ZeroMode::from_flags(self.config.current_config.exclude_zero_grades, &flags) else {
+                return Ok(true);
+            };
             return match *command {
                 "courses" => {
                     self.course_list().await?;
@@ -32,7 +70,7 @@ impl ModuleTrait for CurrentMod {
                 "students" => {
                     if let Some(course) = parsed.get(1) {
                         if let Some(course_id) = self.course_lookup.get(*course) {
-                            self.students(*course_id).await?;
+                            self.students(*course_id, zeros).await?;
                         }
                         else {
                             println!("Invalid Course ID");
@@ -43,10 +81,25 @@ impl ModuleTrait for CurrentMod {
                     }
                     Ok(true)
                 }
+                "student" => {
+                    match (parsed.get(1), parsed.get(2)) {
+                        (Some(course), Some(_)) => {
+                            if let Some(course_id) = self.course_lookup.get(*course) {
+                                self.student(*course_id, &parsed[2..].join(" "), zeros).await?;
+                            }
+                            else {
+                                println!("Invalid Course ID");
+                            }
+                        }
+                        (Some(_), None) => println!("Missing Student Name or ID"),
+                        _ => println!("Missing Course ID")
+                    }
+                    Ok(true)
+                }
                 "grades" => {
                     if let Some(course) = parsed.get(1) {
                         if let Some(course_id) = self.course_lookup.get(*course) {
-                            self.grades(*course_id).await?;
+                            self.grades(*course_id, zeros).await?;
                         }
                         else {
                             println!("Invalid Course ID");
@@ -56,79 +109,233 @@ impl ModuleTrait for CurrentMod {
                         println!("Missing Course ID");
                     }
                     Ok(true)
-                }                
+                }
+                "assignment" => {
+                    match (parsed.get(1), parsed.get(2)) {
+                        (Some(course), Some(_)) => {
+                            if let Some(course_id) = self.course_lookup.get(*course) {
+                                self.assignment(*course_id, &parsed[2..].join(" "), zeros).await?;
+                            }
+                            else {
+                                println!("Invalid Course ID");
+                            }
+                        }
+                        (Some(_), None) => println!("Missing Assignment Name or ID"),
+                        _ => println!("Missing Course ID")
+                    }
+                    Ok(true)
+                }
+                "verify" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.verify(*course_id, zeros).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                "project" | "whatif" => {
+                    match (parsed.get(1), parsed.get(2)) {
+                        (Some(course), Some(_)) => {
+                            if let Some(course_id) = self.course_lookup.get(*course) {
+                                // whatif <course> <student>; <assignment> = <score>; ...
+                                let rest = parsed[2..].join(" ");
+                                let (query, hypothetical) = match *command {
+                                    "whatif" => {
+                                        let mut parts = rest.split(';').map(|x| x.trim());
+                                        (parts.next().unwrap_or(""), parts.filter(|x| !x.is_empty()).collect::<Vec<&str>>())
+                                    }
+                                    _ => (rest.as_str(), Vec::new())
+                                };
+                                if *command == "whatif" && hypothetical.is_empty() {
+                                    println!("Missing Hypothetical Score, e.g. whatif {} {}; Final Exam = 85%", course, query);
+                                }
+                                else {
+                                    self.project(*course_id, query, &hypothetical).await?;
+                                }
+                            }
+                            else {
+                                println!("Invalid Course ID");
+                            }
+                        }
+                        (Some(_), None) => println!("Missing Student Name or ID"),
+                        _ => println!("Missing Course ID")
+                    }
+                    Ok(true)
+                }
+                "trend" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            let query = parsed[2..].join(" ");
+                            self.trend(*course_id, if query.is_empty() { None } else { Some(&query) }).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                "atrisk" => {
+                    match parsed.get(1) {
+                        Some(&"all") => {
+                            let courses = self.config.current_config.courses.clone();
+                            self.atrisk(&courses, zeros).await?;
+                        }
+                        Some(course) => {
+                            if let Some(course_id) = self.course_lookup.get(*course) {
+                                self.atrisk(&[(course.to_string(), *course_id)], zeros).await?;
+                            }
+                            else {
+                                println!("Invalid Course ID");
+                            }
+                        }
+                        None => println!("Missing Course ID")
+                    }
+                    Ok(true)
+                }
                 _ => Ok(false)
             }
         }
         Ok(false)
     }
 
-    async fn refresh(&mut self) -> Result<(),String> {
+    /// Downloads everything first and then replaces the stored data in one
+    /// transaction, so a failure part way through leaves the previous data
+    /// untouched.
+    async fn refresh(&mut self, full : bool) -> Result<(),String> {
         println!("Loading Module: {}", self.get_name());
 
-        Course::drop_table(&self.database).await?;
-        Assignment::drop_table(&self.database).await?;
-        Student::drop_table(&self.database).await?;
-        Submission::drop_table(&self.database).await?;
-
-        Course::create_table(&self.database).await?;
-        Assignment::create_table(&self.database).await?;
-        Student::create_table(&self.database).await?;
-        Submission::create_table(&self.database).await?;
+        let synced = CourseSync::load_all(&self.database).await?;
+        // Incremental requests carry a timestamp, so they can never be replayed
+        let full = full || synced.is_empty() || *self.client.mode() != ApiMode::Live;
+        if full {
+            println!("Full refresh of all submissions");
+        }
+        else {
+            println!("Incremental refresh of submissions (use refresh --full to reload everything)");
+        }
 
         let course_ids = self.course_lookup
             .values()
             .map(|x| x.to_owned())
             .collect::<Vec<i32>>();
 
-        Course::load(&self.database, &self.config, &course_ids).await?;
+        self.client.reset_stats();
+        let courses = Course::fetch(&self.client, &course_ids).await?;
 
-        let mut threads = Vec::<JoinHandle<Result<(),String>>>::new();
+        let mut threads = Vec::<JoinHandle<Result<CourseData,String>>>::new();
         for course in self.config.current_config.courses.iter() {
-            {
-                let d = self.database.clone();
-                let c = self.config.clone();
-                let i = course.1;
-                let t = tokio::spawn(async move {
-                    Student::load(&d, &c, i).await
-                });
-                threads.push(t);
-            }
+            let c = self.client.clone();
+            let i = course.1;
+            let since = if full { None } else { synced.get(&i).copied() };
+            let standard_id = courses.iter()
+                .find(|x| x.id == i)
+                .and_then(|x| x.grading_standard_id);
+            let t = tokio::spawn(async move {
+                // Taken before the download so nothing changed during it is missed
+                let started = Utc::now();
+                let (students, groups, assignments, submissions, cutoffs) = tokio::try_join!(
+                    Student::fetch(&c, i),
+                    AssignmentGroup::fetch(&c, i),
+                    Assignment::fetch(&c, i),
+                    Submission::fetch(&c, i, since),
+                    GradeCutoff::fetch(&c, i, standard_id)
+                )?;
+                Ok(CourseData { course_id : i, started, students, groups, assignments, submissions, cutoffs })
+            });
+            threads.push(t);
+        }
 
-            {
-                let d = self.database.clone();
-                let c = self.config.clone();
-                let i = course.1;
-                let t = tokio::spawn(async move {
-                    Assignment::load(&d, &c, i).await
-                });
-                threads.push(t);
-            }
+        let mut course_data = Vec::<CourseData>::new();
+        for t in threads {
+            let data = t.await
+                .map_err(|e| err!("Refresh Task Failure",e))??;
+            course_data.push(data);
+        }
+        println!("{}", self.client.stats());
 
-            {
-                let d = self.database.clone();
-                let c = self.config.clone();
-                let i = course.1;
-                let t = tokio::spawn(async move {
-                    Submission::load(&d, &c, i).await
-                });
-                threads.push(t);
-            }
+        let mut students = Vec::<Student>::new();
+        let mut groups = Vec::<AssignmentGroup>::new();
+        let mut assignments = Vec::<Assignment>::new();
+        let mut submissions = Vec::<Submission>::new();
+        let mut cutoffs = Vec::<GradeCutoff>::new();
+        for data in course_data.iter_mut() {
+            students.append(&mut data.students);
+            groups.append(&mut data.groups);
+            assignments.append(&mut data.assignments);
+            submissions.append(&mut data.submissions);
+            cutoffs.append(&mut data.cutoffs);
+        }
+        let students = unique_rows(students, |x| (x.course_id, x.id));
+        let groups = unique_rows(groups, |x| x.id);
+        let assignments = unique_rows(assignments, |x| x.id);
+        let cutoffs = unique_rows(cutoffs, |x| (x.course_id, x.name.clone()));
+        // Canvas occasionally returns submissions for assignments it no longer
+        // lists (e.g. deleted ones), which the foreign key would reject
+        let assignment_ids = assignments.iter()
+            .map(|x| x.id)
+            .collect::<HashSet<i32>>();
+        submissions.retain(|x| assignment_ids.contains(&x.assignment_id));
+        let submissions = unique_rows(submissions, |x| x.id);
 
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        if full {
+            CourseSync::clear_table(&mut tx).await?;
         }
 
-        for t in threads {
-            t.await.unwrap()?;
+        let mut stats = Vec::<BulkStats>::new();
+        stats.push(Course::store(&mut tx, &courses).await?);
+        Course::retain(&mut tx, &course_ids).await?;
+        stats.push(GradeCutoff::store(&mut tx, &cutoffs).await?);
+        GradeCutoff::retain(&mut tx, &course_ids, &cutoffs).await?;
+        stats.push(Student::store(&mut tx, &students).await?);
+        Student::retain(&mut tx, &course_ids, &students).await?;
+        let taken_at = Utc::now();
+        let snapshots = students.iter()
+            .map(|x| ScoreSnapshot::from_student(x, taken_at))
+            .collect::<Vec<ScoreSnapshot>>();
+        stats.push(ScoreSnapshot::store(&mut tx, &snapshots).await?);
+        stats.push(AssignmentGroup::store(&mut tx, &groups).await?);
+        AssignmentGroup::retain(&mut tx, &course_ids, &groups).await?;
+        stats.push(Assignment::store(&mut tx, &assignments).await?);
+        Assignment::retain(&mut tx, &course_ids, &assignments).await?;
+        stats.push(Submission::store(&mut tx, &submissions).await?);
+        if full {
+            Submission::retain(&mut tx, &course_ids, &submissions).await?;
         }
-        println!();
+        for data in course_data.iter() {
+            CourseSync::update(&mut tx, data.course_id, data.started).await?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        print_vec(&stats);
 
         Ok(())
     }
 
     fn help(&self) {
         println!("courses");
-        println!("students <course id>");
-        println!("grades <course id>");
+        println!("students <course id> [--zeros|--no-zeros]");
+        println!("student <course id> <name or id> [--zeros|--no-zeros]");
+        println!("grades <course id> [--zeros|--no-zeros]");
+        println!("assignment <course id> <name or id> [--zeros|--no-zeros]");
+        println!("verify <course id> [--zeros|--no-zeros]");
+        println!("atrisk <course id|all> [--zeros|--no-zeros]");
+        println!("project <course id> <name or id>");
+        println!("whatif <course id> <name or id>; <assignment> = <score or percent%>; ...");
+        println!("trend <course id> [name or id]");
+        println!("     --zeros/--no-zeros = count or skip zero scores (default from config)");
         print!("     <course id> =");
         for course in &self.config.current_config.courses {
             print!(" {}", course.0);
@@ -140,12 +347,12 @@ impl ModuleTrait for CurrentMod {
 }
 
 impl CurrentMod {
-    pub fn new(config : Config, database : Pool<Postgres>) -> Self {
+    pub fn new(config : Config, database : Pool<Postgres>, client : CanvasClient) -> Self {
         let mut course_lookup = HashMap::<String,i32>::new();
         for course in config.current_config.courses.iter() {
             course_lookup.insert(course.0.clone(), course.1);
         }
-        Self {config, course_lookup, database }
+        Self {config, course_lookup, database, client }
     }
 
     pub async fn course_list(&self) -> Result<(),String> {
@@ -176,7 +383,7 @@ impl CurrentMod {
         Ok(())
     }
 
-    async fn students(&self, course : i32) -> Result<(),String> {
+    async fn students(&self, course : i32, zeros : ZeroMode) -> Result<(),String> {
         #[derive(sqlx::FromRow)]
         struct Query {
             name : String,
@@ -186,33 +393,40 @@ impl CurrentMod {
             ungraded_init : i64,
             ungraded_resubmit : i64,
             curr_grade : String,
-            curr_score : f32
+            curr_score : Option<Decimal>
         }
         let results = sqlx::query_as::<_,Query>(
                 "
+                WITH sub_counted AS (
+                    SELECT *,
+                        $2 AND COALESCE(score = 0, FALSE) AS skipped,
+                        CASE WHEN $2 AND score = 0 THEN NULL ELSE score END AS counted_score
+                    FROM curr_submissions
+                )
                 SELECT 
                     stu.name,
-                    SUM(CASE WHEN sub.attempt > 0 THEN 1 ELSE 0 END) as submitted,
+                    SUM(CASE WHEN sub.attempt > 0 AND NOT sub.skipped THEN 1 ELSE 0 END) as submitted,
                     SUM(CASE WHEN sub.missing THEN 1 ELSE 0 END) as missing,
                     SUM(CASE WHEN sub.excused THEN 1 ELSE 0 END) as excused,
-                    SUM(CASE WHEN sub.current_submission THEN 0 ELSE 1 END) as something,
                     SUM(CASE WHEN sub.score IS NULL and sub.attempt > 0 THEN 1 ELSE 0 END) as ungraded_init,
                     SUM(CASE WHEN not sub.current_submission THEN 1 ELSE 0 END) as ungraded_resubmit,
                     stu.curr_grade,
                     stu.curr_score
                 FROM curr_students AS stu
-                INNER JOIN curr_submissions AS sub
+                INNER JOIN sub_counted AS sub
                     ON stu.id = sub.user_id
                 INNER JOIN curr_assignments as asn
                     ON asn.id = sub.assignment_id
                 WHERE asn.course_id = $1 and stu.course_id = $1 and asn.points_possible > 0
                 GROUP BY stu.name, stu.curr_grade, stu.curr_score
-                ORDER BY stu.curr_score DESC, stu.name ASC;
+                ORDER BY stu.curr_score DESC NULLS LAST, stu.name ASC;
             ")
             .bind(course)
+            .bind(zeros.exclude)
             .fetch_all(&self.database)
             .await
             .map_err(|e| err!("Course List SQL Query Failure",e))?;
+        println!("{}", zeros);
         println!("{:40} {:4} {:4} {:4} {:4} {:4} {:7} {:5}",
             "NAME","SUBM","MISS","EXCU", "UG-I", "UG-R", "SCORE-%", "GRADE"
         );
@@ -220,104 +434,883 @@ impl CurrentMod {
             "", "", "", "", "", "", "", "",
         );      
         for result in results {
-            println!("{:40} {:4} {:4} {:4} {:4} {:4} {:6.2}% {:5}",
+            println!("{:40} {:4} {:4} {:4} {:4} {:4} {:>7} {:5}",
                 result.name.chars().take(40).collect::<String>(), 
                 result.submitted, result.missing, result.excused,
                 result.ungraded_init, result.ungraded_resubmit,
-                result.curr_score, result.curr_grade
+                result.curr_score.map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()),
+                result.curr_grade
             );
         }
 
         Ok(())
     }
 
-    async fn grades(&self, course : i32) -> Result<(), String> {
+    /// Every assignment for one student, found by Canvas id or (partial or
+    /// misspelled) name, with group subtotals from the grade engine.
+    async fn student(&self, course : i32, query : &str, zeros : ZeroMode) -> Result<(), String> {
+        let gradebook = Gradebook::load(&self.database, course).await?;
+        let Some(student) = CurrentMod::find_student(&gradebook, query) else {
+            return Ok(());
+        };
+        let scheme = GradingScheme::for_course(&self.database, &self.config, course).await?;
+        let options = GradeOptions { exclude_zeros : zeros.exclude, ..GradeOptions::default() };
+        let submissions = gradebook.student_submissions(student.id);
+        let grade = gradebook.grade(submissions, options);
+        let by_assignment = submissions.iter()
+            .map(|x| (x.assignment_id, x))
+            .collect::<HashMap<i32, &SubmissionDef>>();
+
+        println!("{} ({})", student.name, student.id);
+        println!("{}", zeros);
+        println!("{:40} {:7} {:7} {:8} {:3} {:4} {:4} {:4} {:5} {:10}",
+            "ASSIGNMENT", "SCORE", "POINTS", "PERCENT", "ATT", "LATE", "MISS", "EXCU", "MATCH", "NOTE");
+        let divider = || println!("{:-<40} {:-<7} {:-<7} {:-<8} {:-<3} {:-<4} {:-<4} {:-<4} {:-<5} {:-<10}",
+            "", "", "", "", "", "", "", "", "", "");
+        for (group, group_grade) in gradebook.groups.iter().zip(grade.groups.iter()) {
+            divider();
+            println!("{}", group_header(&group.name,
+                if gradebook.weighted { Some(group.group_weight) } else { None },
+                group.drop_lowest, group.drop_highest));
+            for assignment in gradebook.assignments.iter().filter(|x| x.assignment_group_id == group.id) {
+                let submission = by_assignment.get(&assignment.id);
+                let score = submission.and_then(|x| x.score);
+                let flag = |set : bool| if set { "Y" } else { "" };
+                let note = if assignment.omit_from_final_grade {
+                    "omitted"
+                }
+                else if group_grade.dropped.contains(&assignment.id) {
+                    "dropped"
+                }
+                else if submission.is_some_and(|x| x.excused) {
+                    ""
+                }
+                else if score.is_none() {
+                    "ungraded"
+                }
+                else if !group_grade.counted.contains(&assignment.id) {
+                    "zero skipped"
+                }
+                else {
+                    ""
+                };
+                println!("{:40} {:>7} {:7.2} {:>8} {:>3} {:4} {:4} {:4} {:5} {:10}",
+                    assignment.name.chars().take(40).collect::<String>(),
+                    score.map(|x| format!("{:.2}", x.round_dp(2))).unwrap_or("-".to_string()),
+                    assignment.points_possible.round_dp(2),
+                    match score {
+                        Some(x) if !assignment.points_possible.is_zero() =>
+                            format!("{:.2}%", (x / assignment.points_possible * Decimal::ONE_HUNDRED).round_dp(2)),
+                        _ => "-".to_string()
+                    },
+                    submission.map(|x| x.attempt.to_string()).unwrap_or_default(),
+                    flag(submission.is_some_and(|x| x.late)),
+                    flag(submission.is_some_and(|x| x.missing)),
+                    flag(submission.is_some_and(|x| x.excused)),
+                    // An ungraded resubmission shows the score of an earlier attempt
+                    if submission.is_some_and(|x| !x.current_submission) { "N" } else { "" },
+                    note);
+            }
+            println!("{:40} {:7.2} {:7.2} {:>8}",
+                "Subtotal",
+                group_grade.earned.round_dp(2), group_grade.possible.round_dp(2),
+                group_grade.percent
+                    .map(|x| format!("{:.2}%", x.round_dp(2)))
+                    .unwrap_or("-".to_string()));
+        }
+        divider();
+        match grade.percent {
+            Some(percent) => println!("Recomputed: {:.2}% {}", percent.round_dp(2), scheme.letter(percent)),
+            None => println!("Recomputed: nothing graded yet")
+        }
+        match student.curr_score {
+            Some(score) => println!("Canvas:     {:.2}% {}", score.round_dp(2), student.curr_grade),
+            None => println!("Canvas:     nothing graded yet")
+        }
+        Ok(())
+    }
+
+    /// Every student's submission for one assignment, best score first, with
+    /// a histogram of the scores and the students who need attention.
+    async fn assignment(&self, course : i32, query : &str, zeros : ZeroMode) -> Result<(), String> {
+        let gradebook = Gradebook::load(&self.database, course).await?;
+        let candidates = gradebook.assignments.iter()
+            .map(|x| (x.id, x.name.clone()))
+            .collect::<Vec<(i32, String)>>();
+        let Some(id) = CurrentMod::find_match("assignment", query, &candidates) else {
+            return Ok(());
+        };
+        let assignment = gradebook.assignment(id)
+            .ok_or(err!("Assignment Lookup Failure", id))?;
+
+        let mut rows = Vec::<(&StudentDef, Option<&SubmissionDef>)>::new();
+        for student in gradebook.students.iter() {
+            let submission = gradebook.student_submissions(student.id).iter()
+                .find(|x| x.assignment_id == assignment.id);
+            rows.push((student, submission));
+        }
+        // Best score first, then the ungraded, then names
+        rows.sort_by(|a, b| b.1.and_then(|x| x.score).cmp(&a.1.and_then(|x| x.score))
+            .then(a.0.name.cmp(&b.0.name)));
+
+        println!("{} ({}, {} points)", assignment.name,
+            gradebook.group(assignment.assignment_group_id).map(|x| x.name.as_str()).unwrap_or("Unknown Group"),
+            assignment.points_possible.normalize());
+        println!("{}", zeros);
+        println!("{:40} {:7} {:8} {:3} {:4} {:4} {:4} {:4} {:4}",
+            "NAME", "SCORE", "PERCENT", "ATT", "LATE", "MISS", "EXCU", "UG-I", "UG-R");
+        println!("{:-<40} {:-<7} {:-<8} {:-<3} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4}",
+            "", "", "", "", "", "", "", "", "");
+        let mut percents = Vec::<Decimal>::new();
+        let mut missing = Vec::<&str>::new();
+        let mut ungraded_init = Vec::<&str>::new();
+        let mut ungraded_resubmit = Vec::<&str>::new();
+        for (student, submission) in rows.iter() {
+            let score = submission.and_then(|x| x.score);
+            let percent = score
+                .filter(|_| !assignment.points_possible.is_zero())
+                .map(|x| x / assignment.points_possible * Decimal::ONE_HUNDRED);
+            let is_missing = submission.is_some_and(|x| x.missing);
+            let is_ungraded_init = submission.is_some_and(|x| x.score.is_none() && x.attempt > 0);
+            let is_ungraded_resubmit = submission.is_some_and(|x| !x.current_submission);
+            let excused = submission.is_some_and(|x| x.excused);
+            if let Some(percent) = percent {
+                let skipped = excused || (zeros.exclude && percent.is_zero());
+                if !skipped {
+                    percents.push(percent);
+                }
+            }
+            if is_missing {
+                missing.push(&student.name);
+            }
+            if is_ungraded_init {
+                ungraded_init.push(&student.name);
+            }
+            if is_ungraded_resubmit {
+                ungraded_resubmit.push(&student.name);
+            }
+            let flag = |set : bool| if set { "Y" } else { "" };
+            println!("{:40} {:>7} {:>8} {:>3} {:4} {:4} {:4} {:4} {:4}",
+                student.name.chars().take(40).collect::<String>(),
+                score.map(|x| format!("{:.2}", x.round_dp(2))).unwrap_or("-".to_string()),
+                percent.map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()),
+                submission.map(|x| x.attempt.to_string()).unwrap_or_default(),
+                flag(submission.is_some_and(|x| x.late)), flag(is_missing), flag(excused),
+                flag(is_ungraded_init), flag(is_ungraded_resubmit));
+        }
+
+        println!();
+        CurrentMod::print_histogram(&percents);
+        println!();
+        for (label, names) in [("Missing", &missing), ("Ungraded (UG-I)", &ungraded_init),
+                               ("Ungraded resubmission (UG-R)", &ungraded_resubmit)] {
+            println!("{} ({}): {}", label, names.len(),
+                if names.is_empty() { "none".to_string() } else { names.join(", ") });
+        }
+        Ok(())
+    }
+
+    async fn grades(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
         #[derive(sqlx::FromRow)]
         struct Query {
+            id : i32,
             name : String,
             submitted : i64,
             missing : i64,
             excused : i64,
-            grade_a : i64,
-            grade_b : i64,
-            grade_c : i64,
-            grade_d : i64,
-            grade_f : i64,
             grade_zero : i64,
-            avg_score : Option<f64>,
-            avg_grade : Option<f64>,
-            avg_grade_nonzero : Option<f64>,
+            avg_score : Option<Decimal>,
+            avg_grade : Option<Decimal>,
             group : i32,
+            group_name : Option<String>,
+            group_weight : Option<Decimal>,
+            drop_lowest : Option<i32>,
+            drop_highest : Option<i32>,
+            weighted : bool,
             ungraded_init : i64,
             ungraded_resubmit : i64
         }
         let results = sqlx::query_as::<_,Query>(
                 "
+                WITH sub_counted AS (
+                    SELECT *,
+                        $2 AND COALESCE(score = 0, FALSE) AS skipped,
+                        -- Excused work is left out of the averages, as it is from the percentages
+                        CASE WHEN ($2 AND score = 0) OR COALESCE(excused, FALSE) THEN NULL
+                             ELSE score END AS counted_score
+                    FROM curr_submissions
+                )
                 SELECT 
+                    asn.id,
                     asn.name,
-                    SUM(CASE WHEN sub.attempt > 0 THEN 1 ELSE 0 END) as submitted,
+                    SUM(CASE WHEN sub.attempt > 0 AND NOT sub.skipped THEN 1 ELSE 0 END) as submitted,
                     SUM(CASE WHEN sub.missing THEN 1 ELSE 0 END) as missing,
                     SUM(CASE WHEN sub.excused THEN 1 ELSE 0 END) as excused,
-                    SUM(CASE WHEN sub.current_submission THEN 0 ELSE 1 END) as something,
-                    SUM(CASE WHEN sub.score >= (0.9 * asn.points_possible) THEN 1 ELSE 0 END) as grade_a,
-                    SUM(CASE WHEN sub.score >= (0.8 * asn.points_possible) and 
-                                  sub.score < (0.9 * asn.points_possible) THEN 1 ELSE 0 END) as grade_b,
-                    SUM(CASE WHEN sub.score >= (0.7 * asn.points_possible) and 
-                                  sub.score < (0.8 * asn.points_possible) THEN 1 ELSE 0 END) as grade_c,
-                    SUM(CASE WHEN sub.score >= (0.6 * asn.points_possible) and 
-                                  sub.score < (0.7 * asn.points_possible) THEN 1 ELSE 0 END) as grade_d,
-                    SUM(CASE WHEN sub.score > 0 and 
-                                  sub.score < (0.6 * asn.points_possible) THEN 1 ELSE 0 END) as grade_f,
                     SUM(CASE WHEN sub.score = 0 THEN 1 ELSE 0 END) as grade_zero,
-                    AVG(sub.score) as avg_score,
+                    AVG(sub.counted_score) as avg_score,
                     CASE WHEN asn.points_possible = 0 THEN 0 
-                         ELSE AVG(sub.score) / asn.points_possible * 100 END as avg_grade,
-                    CASE WHEN asn.points_possible = 0 THEN 0 
-                         ELSE AVG(CASE WHEN sub.score > 0 THEN sub.score ELSE NULL END) / asn.points_possible * 100 END as avg_grade_nonzero,
+                         ELSE AVG(sub.counted_score) / asn.points_possible * 100 END as avg_grade,
                     asn.assignment_group_id as group,
+                    grp.name as group_name,
+                    grp.group_weight,
+                    grp.drop_lowest,
+                    grp.drop_highest,
+                    crs.weighted,
                     SUM(CASE WHEN sub.score IS NULL and sub.attempt > 0 THEN 1 ELSE 0 END) as ungraded_init,
                     SUM(CASE WHEN not sub.current_submission THEN 1 ELSE 0 END) as ungraded_resubmit
                 FROM curr_assignments AS asn
-                INNER JOIN curr_submissions AS sub
+                INNER JOIN curr_courses AS crs
+                    ON crs.id = asn.course_id
+                INNER JOIN sub_counted AS sub
                     ON asn.id = sub.assignment_id
                 INNER JOIN curr_students as stu
                     ON stu.id = sub.user_id
+                LEFT JOIN curr_assignment_groups AS grp
+                    ON grp.id = asn.assignment_group_id
                 WHERE asn.course_id = $1 and stu.course_id = $1 and asn.points_possible > 0
-                GROUP BY asn.name, asn.points_possible, asn.assignment_group_id
-                ORDER BY asn.assignment_group_id, asn.name;
+                GROUP BY asn.id, asn.name, asn.points_possible, asn.assignment_group_id,
+                         grp.name, grp.group_weight, grp.drop_lowest, grp.drop_highest,
+                         grp.position, crs.weighted
+                ORDER BY grp.position, asn.assignment_group_id, asn.name;
+            ")
+            .bind(course)
+            .bind(zeros.exclude)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Course List SQL Query Failure",e))?;
+
+        let scheme = GradingScheme::for_course(&self.database, &self.config, course).await?;
+        let scores = sqlx::query_as::<_,(i32, Decimal)>(
+                "
+                SELECT sub.assignment_id, sub.score / asn.points_possible * 100
+                FROM curr_submissions AS sub
+                INNER JOIN curr_assignments AS asn
+                    ON asn.id = sub.assignment_id
+                INNER JOIN curr_students AS stu
+                    ON stu.id = sub.user_id
+                WHERE asn.course_id = $1 and stu.course_id = $1
+                    and asn.points_possible > 0 and sub.score IS NOT NULL
+                    and NOT COALESCE(sub.excused, FALSE);
             ")
             .bind(course)
             .fetch_all(&self.database)
             .await
             .map_err(|e| err!("Course List SQL Query Failure",e))?;
-        println!("{:40} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:7} {:8} {:11}",
-            "ASSIGNMENT","SUBM","MISS","EXCU", "UG-I", "UG-R", "GR-A", "GR-B", "GR-C", "GR-D", "GR-F", "ZERO", "AVG-SCR", "AVG-GRD%", "AVG-GRD-N0%"
+        let mut buckets = HashMap::<i32, Vec<i64>>::new();
+        let mut percents = HashMap::<i32, Vec<Decimal>>::new();
+        for (assignment_id, percent) in scores {
+            if zeros.exclude && percent.is_zero() {
+                continue;
+            }
+            percents.entry(assignment_id).or_default().push(percent);
+            // Zero scores have their own column, so only non-zero scores are
+            // bucketed by letter
+            if !percent.is_zero() {
+                buckets.entry(assignment_id)
+                    .or_insert_with(|| vec![0; scheme.cutoffs.len()])[scheme.bucket(percent)] += 1;
+            }
+        }
+        let widths = scheme.cutoffs.iter()
+            .map(|x| (x.0.chars().count() + 3).max(4))
+            .collect::<Vec<usize>>();
+        let letter_header = scheme.cutoffs.iter().zip(widths.iter())
+            .map(|(x, w)| format!(" {:w$}", format!("GR-{}", x.0), w = w))
+            .collect::<String>();
+        let letter_divider = widths.iter()
+            .map(|w| format!(" {:-<w$}", "", w = w))
+            .collect::<String>();
+
+        println!("{}", zeros);
+        println!("{}", scheme);
+        println!("{:40} {:4} {:4} {:4} {:4} {:4}{} {:4} {:7} {:8}",
+            "ASSIGNMENT","SUBM","MISS","EXCU", "UG-I", "UG-R", letter_header, "ZERO", "AVG-SCR", "AVG-GRD%"
         );
         let mut prev_group = -1;
-        for result in results {
+        for result in results.iter() {
             if prev_group != result.group {
-                println!("{:-<40} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4} {:-<7} {:-<8} {:-<11}",
-                    "", "", "", "", "", "", "", "", "", "", "", "", "", "",""
+                println!("{:-<40} {:-<4} {:-<4} {:-<4} {:-<4} {:-<4}{} {:-<4} {:-<7} {:-<8}",
+                    "", "", "", "", "", "", letter_divider, "", "", ""
                 );      
+                println!("{}", group_header(
+                    result.group_name.as_deref().unwrap_or("Unknown Group"),
+                    if result.weighted { result.group_weight } else { None },
+                    result.drop_lowest.unwrap_or(0),
+                    result.drop_highest.unwrap_or(0)));
                 prev_group = result.group;
             }
-            println!("{:40} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:4} {:7.2} {:7.2}% {:10.2}%",
+            let counts = buckets.get(&result.id);
+            let letter_counts = widths.iter().enumerate()
+                .map(|(i, w)| format!(" {:w$}", counts.map(|x| x[i]).unwrap_or(0), w = w))
+                .collect::<String>();
+            println!("{:40} {:4} {:4} {:4} {:4} {:4}{} {:4} {:7.2} {:7.2}%",
                 result.name.chars().take(40).collect::<String>(), 
                 result.submitted, result.missing, result.excused,
                 result.ungraded_init, result.ungraded_resubmit,
-                result.grade_a, result.grade_b, result.grade_c, result.grade_d,
-                result.grade_f, result.grade_zero, 
-                result.avg_score.unwrap_or(0.0),
-                result.avg_grade.unwrap_or(0.0),
-                result.avg_grade_nonzero.unwrap_or(0.0)
+                letter_counts, result.grade_zero, 
+                result.avg_score.unwrap_or_default().round_dp(2),
+                result.avg_grade.unwrap_or_default().round_dp(2)
+            );
+        }
+        println!();
+
+        // Group and course results come from the grade engine, so drop rules
+        // and weights apply the same way they do in Canvas
+        let gradebook = Gradebook::load(&self.database, course).await?;
+        let options = GradeOptions { exclude_zeros : zeros.exclude, ..GradeOptions::default() };
+        let mut group_percents = HashMap::<i32, Vec<Decimal>>::new();
+        let mut course_percents = Vec::<Decimal>::new();
+        for student in gradebook.students.iter() {
+            let grade = gradebook.grade(gradebook.student_submissions(student.id), options);
+            for group in grade.groups.iter() {
+                if let Some(percent) = group.percent {
+                    group_percents.entry(group.group_id).or_default().push(percent);
+                }
+            }
+            if let Some(percent) = grade.percent {
+                course_percents.push(percent);
+            }
+        }
+
+        println!("{:40} {:4} {:7} {:7} {:7} {:7} {:7} {:7} {:7}",
+            "STATISTICS (% OF POINTS)", "N", "MIN", "Q1", "MEDIAN", "Q3", "MAX", "MEAN", "STD-DEV");
+        let divider = || println!("{:-<40} {:-<4} {:-<7} {:-<7} {:-<7} {:-<7} {:-<7} {:-<7} {:-<7}",
+            "", "", "", "", "", "", "", "", "");
+        let mut prev_group = -1;
+        for result in results.iter() {
+            if prev_group != result.group {
+                if let Some(group) = gradebook.group(prev_group) {
+                    CurrentMod::print_summary(&format!("{} Overall", group.name),
+                        group_percents.remove(&prev_group).unwrap_or_default());
+                }
+                divider();
+                println!("{}", result.group_name.as_deref().unwrap_or("Unknown Group"));
+                prev_group = result.group;
+            }
+            CurrentMod::print_summary(&result.name, percents.remove(&result.id).unwrap_or_default());
+        }
+        if let Some(group) = gradebook.group(prev_group) {
+            CurrentMod::print_summary(&format!("{} Overall", group.name),
+                group_percents.remove(&prev_group).unwrap_or_default());
+        }
+        divider();
+        CurrentMod::print_summary("Course Overall", course_percents);
+
+        Ok(())
+
+    }
+
+    /// Bars of the scores in ten point bands, a perfect score counting in the
+    /// top band, followed by the usual statistics.
+    fn print_histogram(percents : &[Decimal]) {
+        const WIDTH : usize = 40;
+        let mut bands = [0usize; 10];
+        for percent in percents.iter() {
+            let band = (percent / Decimal::TEN).floor().to_usize().unwrap_or(0).min(9);
+            bands[band] += 1;
+        }
+        let most = bands.iter().copied().max().unwrap_or(0).max(1);
+        println!("SCORE DISTRIBUTION (% OF POINTS)");
+        for (band, count) in bands.iter().enumerate().rev() {
+            let label = if band == 9 { "90-100".to_string() } else { format!("{}-{}", band * 10, band * 10 + 9) };
+            println!("{:>6} | {:<WIDTH$} {}", label, "#".repeat((count * WIDTH).div_ceil(most)), count);
+        }
+        match Summary::new(percents.to_vec()) {
+            Some(x) => println!("N {}  mean {:.2}%  median {:.2}%  std dev {:.2}",
+                x.count, x.mean.round_dp(2), x.median.round_dp(2), x.std_dev.round_dp(2)),
+            None => println!("Nothing graded yet")
+        }
+    }
+
+    fn print_summary(name : &str, values : Vec<Decimal>) {
+        let name = name.chars().take(40).collect::<String>();
+        match Summary::new(values) {
+            Some(x) => println!("{:40} {:4} {:6.2}% {:6.2}% {:6.2}% {:6.2}% {:6.2}% {:6.2}% {:7.2}",
+                name, x.count, x.min.round_dp(2), x.q1.round_dp(2), x.median.round_dp(2),
+                x.q3.round_dp(2), x.max.round_dp(2), x.mean.round_dp(2), x.std_dev.round_dp(2)),
+            None => println!("{:40} {:4}", name, 0)
+        }
+    }
+
+    /// Recomputes every student's current score from the stored submissions
+    /// and lists the students where it differs from the score Canvas
+    /// reports, with the submissions most likely behind the difference.
+    async fn verify(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
+        let gradebook = Gradebook::load(&self.database, course).await?;
+        let exclude_zeros = zeros.exclude;
+
+        println!("{}", zeros);
+        if exclude_zeros {
+            println!("{:40} {:8} {:8} {:7} {:9}",
+                "NAME", "CANVAS-%", "LOCAL-%", "DIFF", "LOCAL-N0%");
+            println!("{:-<40} {:-<8} {:-<8} {:-<7} {:-<9}",
+                "", "", "", "", "");
+        }
+        else {
+            println!("{:40} {:8} {:8} {:7}",
+                "NAME", "CANVAS-%", "LOCAL-%", "DIFF");
+            println!("{:-<40} {:-<8} {:-<8} {:-<7}",
+                "", "", "", "");
+        }
+        let mut mismatched = 0;
+        for student in gradebook.students.iter() {
+            let submissions = gradebook.student_submissions(student.id);
+            let grade = gradebook.grade(submissions, GradeOptions::default());
+            let local = grade.percent.map(|x| x.round_dp(2));
+            let canvas = student.curr_score.map(|x| x.round_dp(2));
+            // A score on one side only is a mismatch, none on either is not
+            let diff = match (local, canvas) {
+                (Some(local), Some(canvas)) => Some(local - canvas),
+                (None, None) => continue,
+                _ => None
+            };
+            if diff.is_some_and(|x| x.abs() <= Decimal::new(1, 2)) {
+                continue;
+            }
+            mismatched += 1;
+            let nonzero = if exclude_zeros {
+                let options = GradeOptions { exclude_zeros : true, ..GradeOptions::default() };
+                format!("{:8.2}%", gradebook.grade(submissions, options).percent.unwrap_or_default().round_dp(2))
+            }
+            else {
+                String::new()
+            };
+            let percent = |x : Option<Decimal>| x.map(|y| format!("{:.2}%", y)).unwrap_or("-".to_string());
+            println!("{:40} {:>8} {:>8} {:>7} {}",
+                student.name.chars().take(40).collect::<String>(),
+                percent(canvas), percent(local),
+                diff.map(|x| format!("{:.2}", x)).unwrap_or("-".to_string()), nonzero
             );
+
+            let dropped = grade.groups.iter()
+                .flat_map(|x| x.dropped.iter().copied())
+                .collect::<HashSet<i32>>();
+            for submission in submissions {
+                let Some(assignment) = gradebook.assignment(submission.assignment_id) else {
+                    continue;
+                };
+                let reason = if assignment.omit_from_final_grade && submission.score.is_some() {
+                    "omitted from final grade"
+                }
+                else if dropped.contains(&assignment.id) {
+                    "dropped"
+                }
+                else if !submission.current_submission {
+                    "ungraded resubmission"
+                }
+                else if submission.score.is_none() && submission.attempt > 0 {
+                    "submitted, not graded"
+                }
+                else if submission.score.is_none() && submission.missing {
+                    "missing, not graded"
+                }
+                else if assignment.points_possible.is_zero() && submission.score.is_some_and(|x| !x.is_zero()) {
+                    "extra credit (0 points possible)"
+                }
+                else {
+                    continue;
+                };
+                let group = gradebook.group(assignment.assignment_group_id)
+                    .map(|x| x.name.as_str())
+                    .unwrap_or("Unknown Group");
+                println!("    {:36} {:20} {}",
+                    assignment.name.chars().take(36).collect::<String>(),
+                    group.chars().take(20).collect::<String>(), reason);
+            }
+        }
+        println!("{} of {} students match Canvas", gradebook.students.len() - mismatched, gradebook.students.len());
+        Ok(())
+    }
+
+    /// Ranks students by how many risk signals they show: a low current
+    /// score, missing work, zeros on recently due assignments, falling
+    /// scores and no recent submissions.  Thresholds and weights come from
+    /// the atrisk_config section of the config.
+    async fn atrisk(&self, courses : &[(String, i32)], zeros : ZeroMode) -> Result<(), String> {
+        struct Risk {
+            code : String,
+            name : String,
+            risk : Decimal,
+            score : Option<Decimal>,
+            missing : usize,
+            zeros : usize,
+            trend : Option<Decimal>,
+            inactive : Option<i64>,
+            reasons : Vec<String>,
         }
 
+        let settings = &self.config.atrisk_config;
+        let now = Utc::now();
+        let recent = now - Duration::days(settings.recent_days);
+        let mut risks = Vec::<Risk>::new();
+        let mut total = 0;
+        for (code, course) in courses {
+            let gradebook = Gradebook::load(&self.database, *course).await?;
+            let anything_due = gradebook.assignments.iter()
+                .any(|x| x.due_at.is_some_and(|due| due < now));
+            total += gradebook.students.len();
+            for student in gradebook.students.iter() {
+                let mut missing = 0;
+                let mut recent_zeros = 0;
+                let mut graded = Vec::<(DateTime<Utc>, Decimal)>::new();
+                let mut last_submitted : Option<DateTime<Utc>> = None;
+                for submission in gradebook.student_submissions(student.id) {
+                    let Some(assignment) = gradebook.assignment(submission.assignment_id) else {
+                        continue;
+                    };
+                    if submission.submitted_at > last_submitted {
+                        last_submitted = submission.submitted_at;
+                    }
+                    if submission.excused || assignment.omit_from_final_grade || assignment.points_possible.is_zero() {
+                        continue;
+                    }
+                    if submission.missing {
+                        missing += 1;
+                    }
+                    let Some(score) = submission.score else {
+                        continue;
+                    };
+                    if score.is_zero() && assignment.due_at.is_some_and(|due| due >= recent && due <= now) {
+                        recent_zeros += 1;
+                    }
+                    if let Some(due) = assignment.due_at {
+                        if !(zeros.exclude && score.is_zero()) {
+                            graded.push((due, score / assignment.points_possible * Decimal::ONE_HUNDRED));
+                        }
+                    }
+                }
+
+                // Latest trend_window graded scores against the ones before
+                graded.sort_by_key(|x| x.0);
+                let window = settings.trend_window.max(1);
+                let trend = (graded.len() > window).then(|| {
+                    let split = graded.len() - window;
+                    let average = |x : &[(DateTime<Utc>, Decimal)]|
+                        x.iter().map(|y| y.1).sum::<Decimal>() / Decimal::from(x.len());
+                    average(&graded[split..]) - average(&graded[..split])
+                });
+                let inactive = last_submitted.map(|x| (now - x).num_days());
+
+                let mut risk = Decimal::ZERO;
+                let mut reasons = Vec::<String>::new();
+                // No score yet is not a low score
+                if student.curr_score.is_some_and(|x| x < settings.score_threshold) {
+                    risk += settings.score_weight;
+                    reasons.push(format!("score below {}%", settings.score_threshold.normalize()));
+                }
+                if missing >= settings.missing_threshold.max(1) {
+                    risk += settings.missing_weight;
+                    reasons.push(format!("{} missing", missing));
+                }
+                if recent_zeros > 0 {
+                    risk += settings.recent_zero_weight;
+                    reasons.push(format!("{} zero{} in {} days", recent_zeros,
+                        if recent_zeros == 1 { "" } else { "s" }, settings.recent_days));
+                }
+                if let Some(trend) = trend.filter(|x| -*x >= settings.trend_drop) {
+                    risk += settings.trend_weight;
+                    reasons.push(format!("down {:.1} points", -trend.round_dp(1)));
+                }
+                match inactive {
+                    Some(days) if days >= settings.inactive_days => {
+                        risk += settings.inactive_weight;
+                        reasons.push(format!("no submission in {} days", days));
+                    }
+                    None if anything_due => {
+                        risk += settings.inactive_weight;
+                        reasons.push("never submitted".to_string());
+                    }
+                    _ => {}
+                }
+                if risk.is_zero() {
+                    continue;
+                }
+                risks.push(Risk {
+                    code : code.clone(),
+                    name : student.name.clone(),
+                    risk,
+                    score : student.curr_score,
+                    missing,
+                    zeros : recent_zeros,
+                    trend,
+                    inactive,
+                    reasons,
+                });
+            }
+        }
+        // Highest risk first, then the lowest score
+        risks.sort_by(|a, b| b.risk.cmp(&a.risk)
+            .then(a.score.cmp(&b.score))
+            .then(a.name.cmp(&b.name)));
+
+        println!("{}", zeros);
+        println!("{:12} {:30} {:>5} {:>7} {:>4} {:>4} {:>6} {:>5} {:7}",
+            "COURSE", "NAME", "RISK", "SCORE-%", "MISS", "ZERO", "TREND", "LAST", "REASONS");
+        println!("{:-<12} {:-<30} {:-<5} {:-<7} {:-<4} {:-<4} {:-<6} {:-<5} {:-<7}",
+            "", "", "", "", "", "", "", "", "");
+        for x in risks.iter() {
+            println!("{:12} {:30} {:>5} {:>7} {:>4} {:>4} {:>6} {:>5} {}",
+                x.code, x.name.chars().take(30).collect::<String>(),
+                x.risk.normalize().to_string(),
+                x.score.map(|y| format!("{:.2}%", y.round_dp(2))).unwrap_or("-".to_string()),
+                x.missing, x.zeros,
+                x.trend.map(|y| format!("{:+.1}", y.round_dp(1))).unwrap_or("-".to_string()),
+                x.inactive.map(|y| format!("{}d", y)).unwrap_or("-".to_string()),
+                x.reasons.join(", "));
+        }
+        println!("{} of {} students at risk", risks.len(), total);
         Ok(())
+    }
+
+    /// Looks a student or assignment up by Canvas id or name, reporting no
+    /// match or several matches.
+    fn find_match(kind : &str, query : &str, candidates : &[(i32, String)]) -> Option<i32> {
+        let matches = find_by_name(query, candidates);
+        match matches.as_slice() {
+            [] => {
+                println!("No {} matches '{}'", kind, query);
+                None
+            }
+            [(id, _)] => Some(*id),
+            _ => {
+                println!("Several {}s match '{}', use one of the ids:", kind, query);
+                for (id, name) in matches.iter() {
+                    println!("{:>10}  {}", id, name);
+                }
+                None
+            }
+        }
+    }
+
+    fn find_student<'a>(gradebook : &'a Gradebook, query : &str) -> Option<&'a StudentDef> {
+        let candidates = gradebook.students.iter()
+            .map(|x| (x.id, x.name.clone()))
+            .collect::<Vec<(i32, String)>>();
+        let id = CurrentMod::find_match("student", query, &candidates)?;
+        gradebook.students.iter().find(|x| x.id == id)
+    }
+
+    /// Projects a student's final grade from the work still to be graded:
+    /// nothing more earned (worst case), full marks (best case), or each
+    /// assignment at the student's current percentage in its group (current
+    /// pace).  `hypothetical` holds "<assignment> = <score>" entries graded
+    /// first, a score ending in % being a percentage of the points.
+    async fn project(&self, course : i32, query : &str, hypothetical : &[&str]) -> Result<(), String> {
+        let gradebook = Gradebook::load(&self.database, course).await?;
+        let Some(student) = CurrentMod::find_student(&gradebook, query) else {
+            return Ok(());
+        };
+        let candidates = gradebook.assignments.iter()
+            .map(|x| (x.id, x.name.clone()))
+            .collect::<Vec<(i32, String)>>();
+        let mut assumed = HashMap::<i32, Decimal>::new();
+        let mut assumed_lines = Vec::<String>::new();
+        for entry in hypothetical {
+            let Some((name, score)) = entry.split_once('=') else {
+                println!("Expected <assignment> = <score>, found '{}'", entry);
+                return Ok(());
+            };
+            let Some(id) = CurrentMod::find_match("assignment", name.trim(), &candidates) else {
+                return Ok(());
+            };
+            let assignment = gradebook.assignment(id)
+                .ok_or(err!("Assignment Lookup Failure", id))?;
+            let score = score.trim();
+            let points = match score.strip_suffix('%') {
+                Some(percent) => percent.trim().parse::<Decimal>()
+                    .map(|x| x / Decimal::ONE_HUNDRED * assignment.points_possible),
+                None => score.parse::<Decimal>()
+            };
+            let Ok(points) = points else {
+                println!("Invalid Score: {}", score);
+                return Ok(());
+            };
+            assumed.insert(assignment.id, points);
+            assumed_lines.push(format!("What-if: {} = {:.2} / {}", assignment.name,
+                points.round_dp(2), assignment.points_possible.normalize()));
+        }
+
+        let scheme = GradingScheme::for_course(&self.database, &self.config, course).await?;
+        let submissions = gradebook.with_scores(student.id, gradebook.student_submissions(student.id), &assumed);
+        let current = gradebook.grade(&submissions, GradeOptions::default());
+        let remaining = gradebook.remaining(&submissions);
+        let project = |fraction : &dyn Fn(&AssignmentDef) -> Decimal| {
+            gradebook.project(student.id, &submissions, fraction)
+        };
+        let pace = |assignment : &AssignmentDef| {
+            gradebook.groups.iter().zip(current.groups.iter())
+                .find(|x| x.0.id == assignment.assignment_group_id)
+                .and_then(|x| x.1.percent)
+                .or(current.percent)
+                .unwrap_or_default() / Decimal::ONE_HUNDRED
+        };
+        let worst = project(&|_| Decimal::ZERO);
+        let best = project(&|_| Decimal::ONE);
+
+        println!("{} ({})", student.name, student.id);
+        println!("{}", scheme);
+        for line in assumed_lines.iter() {
+            println!("{}", line);
+        }
+        println!();
+        println!("{:40} {:20} {:7} {:10} {:6}",
+            "REMAINING", "GROUP", "POINTS", "DUE", "IMPACT");
+        println!("{:-<40} {:-<20} {:-<7} {:-<10} {:-<6}",
+            "", "", "", "", "");
+        let mut ordered = remaining.clone();
+        ordered.sort_by_key(|x| (x.due_at.is_none(), x.due_at, x.name.clone()));
+        for assignment in ordered.iter() {
+            // How far this one assignment moves the final grade, from zero to
+            // full marks, with everything else at the current pace
+            let full = project(&|x| if x.id == assignment.id { Decimal::ONE } else { pace(x) });
+            let none = project(&|x| if x.id == assignment.id { Decimal::ZERO } else { pace(x) });
+            println!("{:40} {:20} {:7.2} {:10} {:>6}",
+                assignment.name.chars().take(40).collect::<String>(),
+                gradebook.group(assignment.assignment_group_id)
+                    .map(|x| x.name.chars().take(20).collect::<String>())
+                    .unwrap_or("Unknown Group".to_string()),
+                assignment.points_possible.round_dp(2),
+                assignment.due_at.map(|x| x.format("%Y-%m-%d").to_string()).unwrap_or("-".to_string()),
+                match (full, none) {
+                    (Some(full), Some(none)) => format!("{:.2}", (full - none).round_dp(2)),
+                    _ => "-".to_string()
+                });
+        }
+        if remaining.is_empty() {
+            println!("Nothing left to grade");
+        }
 
+        println!();
+        println!("{:30} {:8} {:5}", "PROJECTION", "FINAL-%", "GRADE");
+        println!("{:-<30} {:-<8} {:-<5}", "", "", "");
+        for (label, percent) in [("Current (graded work only)", current.percent), ("Worst case", worst),
+                                 ("Current pace", project(&pace)), ("Best case", best)] {
+            match percent {
+                Some(x) => println!("{:30} {:7.2}% {:5}", label, x.round_dp(2), scheme.letter(x)),
+                None => println!("{:30} {:>8} {:5}", label, "-", "")
+            }
+        }
+
+        // Lowest uniform percentage on the remaining work reaching each letter
+        println!();
+        for (letter, cutoff) in scheme.cutoffs.iter() {
+            let needed = match (worst, best) {
+                (Some(worst), _) if worst >= *cutoff => "secured".to_string(),
+                (_, Some(best)) if best >= *cutoff => {
+                    let (mut low, mut high) = (Decimal::ZERO, Decimal::ONE);
+                    for _ in 0..30 {
+                        let mid = (low + high) / Decimal::TWO;
+                        if project(&|_| mid).is_some_and(|x| x >= *cutoff) {
+                            high = mid;
+                        }
+                        else {
+                            low = mid;
+                        }
+                    }
+                    format!("needs {:.2}% on the remaining work", (high * Decimal::ONE_HUNDRED).round_dp(2))
+                }
+                _ => "out of reach".to_string()
+            };
+            println!("{:5} {:>6}%  {}", letter, cutoff.normalize(), needed);
+        }
+        Ok(())
     }
 
-    
+    /// Score timelines from the snapshots taken on every refresh: one
+    /// student's, or the class average followed by every student, each as a
+    /// sparkline of daily scores and a table by week.
+    async fn trend(&self, course : i32, query : Option<&str>) -> Result<(), String> {
+        let daily = ScoreSnapshot::daily(&self.database, course).await?;
+        if daily.is_empty() {
+            println!("No score snapshots yet, one is taken on every refresh");
+            return Ok(());
+        }
+        let scheme = GradingScheme::for_course(&self.database, &self.config, course).await?;
+        let mut students = Vec::<(i32, String)>::new();
+        for row in daily.iter() {
+            if !students.iter().any(|x| x.0 == row.user_id) {
+                students.push((row.user_id, row.name.clone()));
+            }
+        }
+        students.sort_by(|a, b| a.1.cmp(&b.1));
+        let series = |user_id : i32| daily.iter()
+            .filter(|x| x.user_id == user_id)
+            .map(|x| (x.day, x.curr_score))
+            .collect::<Vec<(NaiveDate, Decimal)>>();
+
+        if let Some(query) = query {
+            let Some(id) = CurrentMod::find_match("student", query, &students) else {
+                return Ok(());
+            };
+            let name = students.iter().find(|x| x.0 == id).map(|x| x.1.as_str()).unwrap_or_default();
+            println!("{} ({})", name, id);
+            CurrentMod::print_timeline(&series(id), &scheme);
+            return Ok(());
+        }
+
+        let mut days = daily.iter().map(|x| x.day).collect::<Vec<NaiveDate>>();
+        days.sort();
+        days.dedup();
+        let average = days.iter()
+            .map(|day| {
+                let scores = daily.iter()
+                    .filter(|x| x.day == *day)
+                    .map(|x| x.curr_score)
+                    .collect::<Vec<Decimal>>();
+                (*day, scores.iter().sum::<Decimal>() / Decimal::from(scores.len()))
+            })
+            .collect::<Vec<(NaiveDate, Decimal)>>();
+        println!("Class average");
+        CurrentMod::print_timeline(&average, &scheme);
+
+        // Biggest drops first
+        let mut rows = students.iter()
+            .map(|x| (x, series(x.0)))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|x| x.1.last().map(|y| y.1).unwrap_or_default() - x.1.first().map(|y| y.1).unwrap_or_default());
+        println!();
+        println!("{:40} {:7} {:7} {:7} {:7} {:7} {:5}",
+            "NAME", "FIRST-%", "LAST-%", "CHANGE", "LOW-%", "HIGH-%", "DAILY");
+        println!("{:-<40} {:-<7} {:-<7} {:-<7} {:-<7} {:-<7} {:-<5}",
+            "", "", "", "", "", "", "");
+        for ((_, name), points) in rows.iter() {
+            let scores = points.iter().map(|x| x.1).collect::<Vec<Decimal>>();
+            let first = scores.first().copied().unwrap_or_default();
+            let last = scores.last().copied().unwrap_or_default();
+            println!("{:40} {:6.2}% {:6.2}% {:>7} {:6.2}% {:6.2}% {}",
+                name.chars().take(40).collect::<String>(),
+                first.round_dp(2), last.round_dp(2), format!("{:+.2}", (last - first).round_dp(2)),
+                scores.iter().min().copied().unwrap_or_default().round_dp(2),
+                scores.iter().max().copied().unwrap_or_default().round_dp(2),
+                sparkline(&scores[scores.len().saturating_sub(TREND_DAYS)..]));
+        }
+        Ok(())
+    }
 
+    /// A sparkline of the latest daily scores, then the score at the end of
+    /// each week (weeks start on Monday).
+    fn print_timeline(points : &[(NaiveDate, Decimal)], scheme : &GradingScheme) {
+        let scores = points.iter().map(|x| x.1).collect::<Vec<Decimal>>();
+        let shown = &scores[scores.len().saturating_sub(TREND_DAYS)..];
+        println!("{} ({} days, {:.2}% to {:.2}%)", sparkline(shown), shown.len(),
+            shown.iter().min().copied().unwrap_or_default().round_dp(2),
+            shown.iter().max().copied().unwrap_or_default().round_dp(2));
 
-}
\ No newline at end of file
+        let mut weeks = Vec::<(NaiveDate, Decimal)>::new();
+        for (day, score) in points.iter() {
+            let week = *day - Duration::days(day.weekday().num_days_from_monday() as i64);
+            match weeks.last_mut() {
+                Some(last) if last.0 == week => last.1 = *score,
+                _ => weeks.push((week, *score))
+            }
+        }
+        println!("{:10} {:8} {:5} {:7}", "WEEK OF", "SCORE-%", "GRADE", "CHANGE");
+        println!("{:-<10} {:-<8} {:-<5} {:-<7}", "", "", "", "");
+        let mut previous : Option<Decimal> = None;
+        for (week, score) in weeks.iter() {
+            println!("{:10} {:7.2}% {:5} {:>7}",
+                week.format("%Y-%m-%d").to_string(), score.round_dp(2), scheme.letter(*score),
+                previous.map(|x| format!("{:+.2}", (*score - x).round_dp(2))).unwrap_or("-".to_string()));
+            previous = Some(*score);
+        }
+    }
+}
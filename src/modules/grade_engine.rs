@@ -0,0 +1,514 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use sqlx::{Pool, Postgres};
+use crate::macros::err;
+
+/// How ungraded and zero scores are treated when computing a grade.
+#[derive(Clone, Copy, Default)]
+pub struct GradeOptions {
+    // Zero scores are not a real attempt and are left out (exclude_zero_grades)
+    pub exclude_zeros : bool,
+    // Ungraded work counts as zero, like Canvas's final (not current) score
+    pub ungraded_as_zero : bool,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct GroupDef {
+    pub id : i32,
+    pub name : String,
+    pub position : i32,
+    pub group_weight : Decimal,
+    pub drop_lowest : i32,
+    pub drop_highest : i32,
+    pub never_drop : Vec<i32>,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct AssignmentDef {
+    pub id : i32,
+    pub name : String,
+    pub assignment_group_id : i32,
+    pub points_possible : Decimal,
+    pub omit_from_final_grade : bool,
+    pub due_at : Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct StudentDef {
+    pub id : i32,
+    pub name : String,
+    pub curr_grade : String,
+    pub curr_score : Option<Decimal>,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct SubmissionDef {
+    pub user_id : i32,
+    pub assignment_id : i32,
+    pub score : Option<Decimal>,
+    pub excused : bool,
+    pub missing : bool,
+    pub late : bool,
+    pub attempt : i32,
+    pub current_submission : bool,
+    pub submitted_at : Option<DateTime<Utc>>,
+}
+
+/// Result for one assignment group.  `percent` is None when nothing in the
+/// group counted, in which case the group carries no weight.
+pub struct GroupGrade {
+    pub group_id : i32,
+    pub earned : Decimal,
+    pub possible : Decimal,
+    pub percent : Option<Decimal>,
+    pub counted : Vec<i32>,
+    pub dropped : Vec<i32>,
+}
+
+pub struct CourseGrade {
+    pub percent : Option<Decimal>,
+    pub groups : Vec<GroupGrade>,
+}
+
+/// Everything needed to recompute grades for one course, loaded from the
+/// curr_* tables.
+pub struct Gradebook {
+    pub course_id : i32,
+    pub weighted : bool,
+    pub groups : Vec<GroupDef>,
+    pub assignments : Vec<AssignmentDef>,
+    pub students : Vec<StudentDef>,
+    pub submissions : HashMap<i32, Vec<SubmissionDef>>,
+}
+
+impl Gradebook {
+
+    pub async fn load(database : &Pool<Postgres>, course_id : i32) -> Result<Self, String> {
+        let weighted = sqlx::query_scalar::<_,bool>(
+                "
+                SELECT weighted FROM curr_courses WHERE id = $1;
+            ")
+            .bind(course_id)
+            .fetch_optional(database)
+            .await
+            .map_err(|e| err!("Gradebook SQL Query Failure",e))?
+            .unwrap_or(false);
+
+        let groups = sqlx::query_as::<_,GroupDef>(
+                "
+                SELECT id, name, COALESCE(position, 0) AS position,
+                    COALESCE(group_weight, 0) AS group_weight,
+                    COALESCE(drop_lowest, 0) AS drop_lowest,
+                    COALESCE(drop_highest, 0) AS drop_highest,
+                    COALESCE(never_drop, '{}') AS never_drop
+                FROM curr_assignment_groups
+                WHERE course_id = $1
+                ORDER BY position, id;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Gradebook SQL Query Failure",e))?;
+
+        let assignments = sqlx::query_as::<_,AssignmentDef>(
+                "
+                SELECT id, name, assignment_group_id, points_possible, omit_from_final_grade, due_at
+                FROM curr_assignments
+                WHERE course_id = $1
+                ORDER BY assignment_group_id, name;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Gradebook SQL Query Failure",e))?;
+
+        let students = sqlx::query_as::<_,StudentDef>(
+                "
+                SELECT id, name, curr_grade, curr_score
+                FROM curr_students
+                WHERE course_id = $1
+                ORDER BY name;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Gradebook SQL Query Failure",e))?;
+
+        let rows = sqlx::query_as::<_,SubmissionDef>(
+                "
+                SELECT sub.user_id, sub.assignment_id, sub.score,
+                    COALESCE(sub.excused, FALSE) AS excused,
+                    COALESCE(sub.missing, FALSE) AS missing,
+                    COALESCE(sub.late, FALSE) AS late,
+                    COALESCE(sub.attempt, 0) AS attempt,
+                    COALESCE(sub.current_submission, TRUE) AS current_submission,
+                    sub.submitted_at
+                FROM curr_submissions AS sub
+                INNER JOIN curr_assignments AS asn
+                    ON asn.id = sub.assignment_id
+                WHERE asn.course_id = $1;
+            ")
+            .bind(course_id)
+            .fetch_all(database)
+            .await
+            .map_err(|e| err!("Gradebook SQL Query Failure",e))?;
+        let mut submissions = HashMap::<i32, Vec<SubmissionDef>>::new();
+        for row in rows {
+            submissions.entry(row.user_id).or_default().push(row);
+        }
+
+        Ok(Self { course_id, weighted, groups, assignments, students, submissions })
+    }
+
+    pub fn assignment(&self, id : i32) -> Option<&AssignmentDef> {
+        self.assignments.iter().find(|x| x.id == id)
+    }
+
+    pub fn group(&self, id : i32) -> Option<&GroupDef> {
+        self.groups.iter().find(|x| x.id == id)
+    }
+
+    pub fn student_submissions(&self, user_id : i32) -> &[SubmissionDef] {
+        self.submissions.get(&user_id).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    /// Work that can still change a student's grade: assignments that count
+    /// toward the grade and have no score yet.
+    pub fn remaining(&self, submissions : &[SubmissionDef]) -> Vec<&AssignmentDef> {
+        self.assignments.iter()
+            .filter(|x| !x.omit_from_final_grade && !x.points_possible.is_zero())
+            .filter(|x| !submissions.iter()
+                .any(|y| y.assignment_id == x.id && (y.excused || y.score.is_some())))
+            .collect()
+    }
+
+    /// A student's submissions as if the assignments in `scores` were graded
+    /// with those scores, for projections and what-if grades.
+    pub fn with_scores(&self, user_id : i32, submissions : &[SubmissionDef], scores : &HashMap<i32, Decimal>) -> Vec<SubmissionDef> {
+        let mut result = submissions.iter()
+            .filter(|x| !scores.contains_key(&x.assignment_id))
+            .cloned()
+            .collect::<Vec<SubmissionDef>>();
+        for (assignment_id, score) in scores.iter() {
+            let existing = submissions.iter().find(|x| x.assignment_id == *assignment_id);
+            result.push(SubmissionDef {
+                user_id,
+                assignment_id : *assignment_id,
+                score : Some(*score),
+                excused : false,
+                missing : false,
+                late : existing.is_some_and(|x| x.late),
+                attempt : existing.map(|x| x.attempt).unwrap_or(0),
+                current_submission : true,
+                submitted_at : existing.and_then(|x| x.submitted_at),
+            });
+        }
+        result
+    }
+
+    /// Final percentage with each remaining assignment graded at the fraction
+    /// of its points that `fraction` returns for it.
+    pub fn project(&self, user_id : i32, submissions : &[SubmissionDef], fraction : &dyn Fn(&AssignmentDef) -> Decimal) -> Option<Decimal> {
+        let scores = self.remaining(submissions).into_iter()
+            .map(|x| (x.id, x.points_possible * fraction(x)))
+            .collect::<HashMap<i32, Decimal>>();
+        let options = GradeOptions { ungraded_as_zero : true, ..GradeOptions::default() };
+        self.grade(&self.with_scores(user_id, submissions, &scores), options).percent
+    }
+
+    /// Recomputes a student's course percentage the way Canvas does: excused
+    /// and omitted work never counts, ungraded work counts only when
+    /// `ungraded_as_zero` is set, drop rules keep the scores that give the
+    /// best (or for drop highest, worst) group result, and in a weighted
+    /// course groups with nothing counted are left out of the total weight.
+    pub fn grade(&self, submissions : &[SubmissionDef], options : GradeOptions) -> CourseGrade {
+        let by_assignment = submissions.iter()
+            .map(|x| (x.assignment_id, x))
+            .collect::<HashMap<i32, &SubmissionDef>>();
+
+        let mut groups = Vec::<GroupGrade>::new();
+        for group in self.groups.iter() {
+            let mut scores = Vec::<(i32, Decimal, Decimal)>::new();
+            for assignment in self.assignments.iter().filter(|x| x.assignment_group_id == group.id) {
+                if assignment.omit_from_final_grade {
+                    continue;
+                }
+                let submission = by_assignment.get(&assignment.id);
+                if submission.is_some_and(|x| x.excused) {
+                    continue;
+                }
+                let score = match submission.and_then(|x| x.score) {
+                    Some(score) => score,
+                    None if options.ungraded_as_zero => Decimal::ZERO,
+                    None => continue
+                };
+                if options.exclude_zeros && score.is_zero() && !assignment.points_possible.is_zero() {
+                    continue;
+                }
+                scores.push((assignment.id, score, assignment.points_possible));
+            }
+            groups.push(Gradebook::group_grade(group, scores));
+        }
+
+        let percent = if self.weighted {
+            let mut total = Decimal::ZERO;
+            let mut total_weight = Decimal::ZERO;
+            for (group, grade) in self.groups.iter().zip(groups.iter()) {
+                if let Some(percent) = grade.percent {
+                    total += percent * group.group_weight;
+                    total_weight += group.group_weight;
+                }
+            }
+            if total_weight.is_zero() { None } else { Some(total / total_weight) }
+        }
+        else {
+            let earned = groups.iter().map(|x| x.earned).sum::<Decimal>();
+            let possible = groups.iter().map(|x| x.possible).sum::<Decimal>();
+            if possible.is_zero() { None } else { Some(earned / possible * Decimal::ONE_HUNDRED) }
+        };
+        CourseGrade { percent, groups }
+    }
+
+    fn group_grade(group : &GroupDef, scores : Vec<(i32, Decimal, Decimal)>) -> GroupGrade {
+        let never_drop = group.never_drop.iter().copied().collect::<HashSet<i32>>();
+        let (fixed, droppable) : (Vec<_>, Vec<_>) = scores.into_iter()
+            .partition(|x| never_drop.contains(&x.0));
+
+        // Canvas always keeps at least one droppable score
+        let mut kept = droppable;
+        let mut dropped = Vec::<i32>::new();
+        let drop_lowest = (group.drop_lowest.max(0) as usize).min(kept.len().saturating_sub(1));
+        if drop_lowest > 0 {
+            let keep = kept.len() - drop_lowest;
+            let (k, d) = Gradebook::keep_best(kept, &fixed, keep, true);
+            kept = k;
+            dropped.extend(d);
+        }
+        let drop_highest = (group.drop_highest.max(0) as usize).min(kept.len().saturating_sub(1));
+        if drop_highest > 0 {
+            let keep = kept.len() - drop_highest;
+            let (k, d) = Gradebook::keep_best(kept, &fixed, keep, false);
+            kept = k;
+            dropped.extend(d);
+        }
+
+        let counted = fixed.iter().chain(kept.iter()).collect::<Vec<_>>();
+        let earned = counted.iter().map(|x| x.1).sum::<Decimal>();
+        let possible = counted.iter().map(|x| x.2).sum::<Decimal>();
+        let percent = if possible.is_zero() {
+            None
+        }
+        else {
+            Some(earned / possible * Decimal::ONE_HUNDRED)
+        };
+        GroupGrade {
+            group_id : group.id,
+            earned,
+            possible,
+            percent,
+            counted : counted.iter().map(|x| x.0).collect(),
+            dropped,
+        }
+    }
+
+    /// Chooses which `keep` scores to keep so the group ratio (including the
+    /// never-drop scores) is as high as possible (`maximize`) or as low as
+    /// possible.  Sorting by percentage is not enough when point values
+    /// differ, so this searches for the best achievable ratio q: a ratio is
+    /// achievable when the `keep` largest values of sign * (score - q *
+    /// possible) plus the fixed scores sum to at least zero, sign being -1
+    /// when minimizing.
+    fn keep_best(scores : Vec<(i32, Decimal, Decimal)>, fixed : &[(i32, Decimal, Decimal)],
+                 keep : usize, maximize : bool) -> (Vec<(i32, Decimal, Decimal)>, Vec<i32>) {
+        let sign = if maximize { 1.0 } else { -1.0 };
+        let as_f64 = |x : Decimal| x.to_f64().unwrap_or(0.0);
+        let select = |q : f64| {
+            let mut order = scores.iter()
+                .map(|x| (sign * (as_f64(x.1) - q * as_f64(x.2)), x.0))
+                .collect::<Vec<(f64, i32)>>();
+            order.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            let fixed_total = fixed.iter()
+                .map(|x| sign * (as_f64(x.1) - q * as_f64(x.2)))
+                .sum::<f64>();
+            let total = fixed_total + order.iter().take(keep).map(|x| x.0).sum::<f64>();
+            (total, order.into_iter().take(keep).map(|x| x.1).collect::<HashSet<i32>>())
+        };
+
+        let max_ratio = scores.iter().chain(fixed.iter())
+            .filter(|x| !x.2.is_zero())
+            .map(|x| as_f64(x.1) / as_f64(x.2))
+            .fold(1.0, f64::max);
+        // Achievable ratios lie below the best one when maximizing and above
+        // it when minimizing, so the search closes in from that side
+        let (mut low, mut high) = (0.0, max_ratio);
+        let mut achieved = if maximize { low } else { high };
+        for _ in 0..60 {
+            let mid = (low + high) / 2.0;
+            let achievable = select(mid).0 >= 0.0;
+            if achievable {
+                achieved = mid;
+            }
+            if achievable == maximize {
+                low = mid;
+            }
+            else {
+                high = mid;
+            }
+        }
+        let (_, keep_ids) = select(achieved);
+        let (kept, dropped) : (Vec<_>, Vec<_>) = scores.into_iter()
+            .partition(|x| keep_ids.contains(&x.0));
+        (kept, dropped.into_iter().map(|x| x.0).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(drop_lowest : i32, drop_highest : i32, never_drop : Vec<i32>) -> GroupDef {
+        GroupDef {
+            id : 1,
+            name : "Quizzes".to_string(),
+            position : 0,
+            group_weight : Decimal::ZERO,
+            drop_lowest,
+            drop_highest,
+            never_drop,
+        }
+    }
+
+    fn scores(x : &[(i32, i64, i64)]) -> Vec<(i32, Decimal, Decimal)> {
+        x.iter().map(|y| (y.0, Decimal::from(y.1), Decimal::from(y.2))).collect()
+    }
+
+    #[test]
+    fn drop_lowest_with_unequal_points() {
+        // Dropping the lowest percentage (2/10) leaves 49/110, dropping the
+        // 40/100 leaves 11/20
+        let grade = Gradebook::group_grade(&group(1, 0, vec![]), scores(&[(1, 9, 10), (2, 40, 100), (3, 2, 10)]));
+        assert_eq!(grade.dropped, vec![2]);
+        assert_eq!(grade.earned, Decimal::from(11));
+        assert_eq!(grade.possible, Decimal::from(20));
+    }
+
+    #[test]
+    fn drop_highest_with_unequal_points() {
+        // Dropping the 20/20 leaves the lowest ratio, 59/110
+        let grade = Gradebook::group_grade(&group(0, 1, vec![]), scores(&[(1, 9, 10), (2, 50, 100), (3, 20, 20)]));
+        assert_eq!(grade.dropped, vec![3]);
+        assert_eq!(grade.earned, Decimal::from(59));
+        assert_eq!(grade.possible, Decimal::from(110));
+    }
+
+    #[test]
+    fn drop_lowest_with_never_drop() {
+        // Next to a never-drop 0/100, keeping 60/100 (60/200) beats keeping
+        // 10/10 (10/110)
+        let grade = Gradebook::group_grade(&group(1, 0, vec![3]), scores(&[(1, 10, 10), (2, 60, 100), (3, 0, 100)]));
+        assert_eq!(grade.dropped, vec![1]);
+        assert_eq!(grade.counted.len(), 2);
+        assert!(grade.counted.contains(&3));
+        assert_eq!(grade.earned, Decimal::from(60));
+        assert_eq!(grade.possible, Decimal::from(200));
+    }
+
+    #[test]
+    fn drop_highest_with_never_drop() {
+        // Next to a never-drop 100/100, keeping 50/100 (150/200) is lower
+        // than keeping 1/10 (101/110)
+        let grade = Gradebook::group_grade(&group(0, 1, vec![3]), scores(&[(1, 1, 10), (2, 50, 100), (3, 100, 100)]));
+        assert_eq!(grade.dropped, vec![1]);
+        assert_eq!(grade.earned, Decimal::from(150));
+        assert_eq!(grade.possible, Decimal::from(200));
+    }
+
+    #[test]
+    fn drop_keeps_one_droppable_score() {
+        let grade = Gradebook::group_grade(&group(2, 0, vec![]), scores(&[(1, 5, 10), (2, 8, 10)]));
+        assert_eq!(grade.dropped, vec![1]);
+        assert_eq!(grade.counted, vec![2]);
+    }
+
+    // Unweighted course with one group: assignment 1 is worth 10 points, 2 is
+    // worth 10, 3 is worth 20 and 4 is omitted from the final grade
+    fn gradebook() -> Gradebook {
+        let assignment = |id : i32, points : i64, omit : bool| AssignmentDef {
+            id,
+            name : format!("Assignment {}", id),
+            assignment_group_id : 1,
+            points_possible : Decimal::from(points),
+            omit_from_final_grade : omit,
+            due_at : None,
+        };
+        Gradebook {
+            course_id : 1,
+            weighted : false,
+            groups : vec![group(0, 0, vec![])],
+            assignments : vec![assignment(1, 10, false), assignment(2, 10, false),
+                               assignment(3, 20, false), assignment(4, 10, true)],
+            students : vec![],
+            submissions : HashMap::new(),
+        }
+    }
+
+    fn submission(assignment_id : i32, score : Option<i64>, excused : bool) -> SubmissionDef {
+        SubmissionDef {
+            user_id : 5001,
+            assignment_id,
+            score : score.map(Decimal::from),
+            excused,
+            missing : false,
+            late : true,
+            attempt : 1,
+            current_submission : true,
+            submitted_at : None,
+        }
+    }
+
+    #[test]
+    fn remaining_leaves_out_graded_excused_and_omitted() {
+        let gradebook = gradebook();
+        let submissions = vec![submission(1, Some(8), false), submission(2, None, true)];
+        let remaining = gradebook.remaining(&submissions).iter().map(|x| x.id).collect::<Vec<i32>>();
+        assert_eq!(remaining, vec![3]);
+    }
+
+    #[test]
+    fn remaining_work_at_no_and_full_marks() {
+        // 8/10 graded, 30 points to go
+        let gradebook = gradebook();
+        let submissions = vec![submission(1, Some(8), false)];
+        assert_eq!(gradebook.project(5001, &submissions, &|_| Decimal::ZERO), Some(Decimal::from(20)));
+        assert_eq!(gradebook.project(5001, &submissions, &|_| Decimal::ONE), Some(Decimal::from(95)));
+        assert_eq!(gradebook.grade(&submissions, GradeOptions::default()).percent, Some(Decimal::from(80)));
+    }
+
+    #[test]
+    fn nothing_remaining_projects_the_current_grade() {
+        let gradebook = gradebook();
+        let submissions = vec![submission(1, Some(8), false), submission(2, Some(6), false),
+                               submission(3, Some(16), false)];
+        assert!(gradebook.remaining(&submissions).is_empty());
+        assert_eq!(gradebook.project(5001, &submissions, &|_| Decimal::ZERO), Some(Decimal::from(75)));
+        assert_eq!(gradebook.project(5001, &submissions, &|_| Decimal::ONE), Some(Decimal::from(75)));
+    }
+
+    #[test]
+    fn overrides_replace_existing_scores() {
+        let gradebook = gradebook();
+        let submissions = vec![submission(1, Some(8), false), submission(2, None, true)];
+        let overrides = HashMap::from([(1, Decimal::from(10)), (2, Decimal::from(5))]);
+        let mut result = gradebook.with_scores(5001, &submissions, &overrides);
+        result.sort_by_key(|x| x.assignment_id);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].score, Some(Decimal::from(10)));
+        assert!(result[0].late);
+        // An override grades excused work, so it counts again
+        assert_eq!(result[1].score, Some(Decimal::from(5)));
+        assert!(!result[1].excused);
+        assert_eq!(gradebook.grade(&result, GradeOptions::default()).percent, Some(Decimal::from(75)));
+    }
+}
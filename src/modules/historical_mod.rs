@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::task::JoinHandle;
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use crate::data::assignment::Assignment;
+use crate::data::assignment_group::AssignmentGroup;
+use crate::data::bulk::unique_rows;
+use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
+use crate::data::course::Course;
+use crate::data::history::{HistAssignment, HistCourse, HistStudent, HistSubmission};
+use crate::data::student::Student;
+use crate::data::submission::Submission;
+use crate::modules::module::{print_vec, ModuleTrait, ZeroMode};
+use crate::modules::statistics::Summary;
+use crate::macros::err;
+
+// Lower bound of each band in the assignment score distributions
+const BANDS : [(&str, i64); 5] = [("90+", 90), ("80s", 80), ("70s", 70), ("60s", 60), ("<60", 0)];
+
+// Letters in the order the final grade distribution lists them, any other
+// letter a scheme uses follows alphabetically
+const LETTERS : [&str; 12] = ["A", "A-", "B+", "B", "B-", "C+", "C", "C-", "D+", "D", "D-", "F"];
+
+const COMMANDS : [&str; 3] = ["terms", "trends", "grades"];
+
+// The scores of one assignment in one term
+#[derive(Default)]
+struct TermResults {
+    students : i64,
+    missing : i64,
+    percents : Vec<Decimal>,
+}
+
+pub struct HistoricalMod {
+    config : Config,
+    database : Pool<Postgres>,
+    client : CanvasClient,
+}
+
+#[async_trait]
+impl ModuleTrait for HistoricalMod {
+    fn get_name(&self) -> String {
+        "Historical".to_string()
+    }
+
+    async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        let (flags, parsed) : (Vec<&str>, Vec<&str>) = parsed.into_iter()
+            .partition(|x| x.starts_with("--"));
+        if let Some(command) = parsed.first() {
+            if !COMMANDS.contains(command) {
+                return Ok(false);
+            }
+            let Some(zeros) = ZeroMode::from_flags(self.config.current_config.exclude_zero_grades, &flags) else {
+                return Ok(true);
+            };
+            return match *command {
+                "terms" => {
+                    self.terms().await?;
+                    Ok(true)
+                }
+                "trends" => {
+                    if let Some(code) = parsed.get(1) {
+                        self.trends(code, &parsed[2..].join(" "), zeros).await?;
+                    }
+                    else {
+                        println!("Missing Course Code");
+                    }
+                    Ok(true)
+                }
+                "grades" => {
+                    if let Some(code) = parsed.get(1) {
+                        self.grades(code).await?;
+                    }
+                    else {
+                        println!("Missing Course Code");
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    /// Archives the concluded courses the user teaches.  Courses already in
+    /// the archive are skipped unless `full` is set, since a concluded course
+    /// no longer changes.
+    async fn refresh(&mut self, full : bool) -> Result<(),String> {
+        println!("Loading Module: {}", self.get_name());
+
+        self.client.reset_stats();
+        let archived = HistCourse::archived(&self.database).await?
+            .into_iter()
+            .collect::<HashSet<i32>>();
+        let courses = Course::fetch_concluded(&self.client).await?
+            .into_iter()
+            .filter(|x| full || !archived.contains(&x.id))
+            .collect::<Vec<Course>>();
+        if courses.is_empty() {
+            println!("{}", self.client.stats());
+            println!("No newly concluded courses, use refresh --full to archive them again");
+            return Ok(());
+        }
+
+        type Downloaded = (Vec<Student>, Vec<HistAssignment>, Vec<HistSubmission>);
+        let mut threads = Vec::<JoinHandle<Result<Downloaded,String>>>::new();
+        for course in courses.iter() {
+            let c = self.client.clone();
+            let i = course.id;
+            threads.push(tokio::spawn(async move {
+                let students = Student::fetch(&c, i).await?;
+                let groups = AssignmentGroup::fetch(&c, i).await?;
+                let assignments = Assignment::fetch(&c, i).await?
+                    .iter()
+                    .map(|x| HistAssignment::from_assignment(x, &groups))
+                    .collect::<Vec<HistAssignment>>();
+                let submissions = Submission::fetch_concluded(&c, i).await?
+                    .iter()
+                    .map(|x| HistSubmission::from_submission(i, x))
+                    .collect::<Vec<HistSubmission>>();
+                Ok((students, assignments, submissions))
+            }));
+        }
+        let mut students = Vec::<HistStudent>::new();
+        let mut assignments = Vec::<HistAssignment>::new();
+        let mut submissions = Vec::<HistSubmission>::new();
+        for t in threads {
+            let (s, mut a, mut u) = t.await
+                .map_err(|e| err!("Refresh Task Failure",e))??;
+            students.extend(s.iter().map(HistStudent::from_student));
+            assignments.append(&mut a);
+            submissions.append(&mut u);
+        }
+        println!("{}", self.client.stats());
+        let hist_courses = courses.iter()
+            .map(HistCourse::from_course)
+            .collect::<Vec<HistCourse>>();
+        let students = unique_rows(students, |x| (x.course_id, x.id));
+        let assignments = unique_rows(assignments, |x| (x.course_id, x.id));
+        // Submissions for assignments Canvas did not list cannot be archived
+        let assignment_ids = assignments.iter()
+            .map(|x| (x.course_id, x.id))
+            .collect::<HashSet<(i32, i32)>>();
+        let submissions = unique_rows(submissions, |x| (x.course_id, x.assignment_id, x.user_id))
+            .into_iter()
+            .filter(|x| assignment_ids.contains(&(x.course_id, x.assignment_id)))
+            .collect::<Vec<HistSubmission>>();
+
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        let stats = vec![
+            HistCourse::replace(&mut tx, &hist_courses).await?,
+            HistStudent::store(&mut tx, &students).await?,
+            HistAssignment::store(&mut tx, &assignments).await?,
+            HistSubmission::store(&mut tx, &submissions).await?,
+        ];
+        tx.commit()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        print_vec(&stats);
+
+        Ok(())
+    }
+
+    fn help(&self) {
+        println!("terms");
+        println!("trends <course code> [assignment name] [--zeros|--no-zeros]");
+        println!("grades <course code>");
+        println!("     <course code> = a code from terms, without spaces (e.g. CSE110)");
+        println!("     terms marked * are the current term, from the Current module");
+    }
+
+}
+
+impl HistoricalMod {
+    pub fn new(config : Config, database : Pool<Postgres>, client : CanvasClient) -> Self {
+        Self { config, database, client }
+    }
+
+    /// Course codes are matched ignoring case and spaces, so "CSE110" finds
+    /// "CSE 110".
+    fn normalize_code(code : &str) -> String {
+        code.chars()
+            .filter(|x| !x.is_whitespace())
+            .collect::<String>()
+            .to_uppercase()
+    }
+
+    async fn terms(&self) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            code : String,
+            term : String,
+            students : i64,
+            assignments : i64,
+            avg_final : Option<Decimal>,
+        }
+        let results = sqlx::query_as::<_,Query>(
+                "
+                SELECT crs.code, crs.term,
+                    (SELECT COUNT(*) FROM hist_students AS stu WHERE stu.course_id = crs.id) AS students,
+                    (SELECT COUNT(*) FROM hist_assignments AS asn WHERE asn.course_id = crs.id) AS assignments,
+                    (SELECT AVG(final_score) FROM hist_students AS stu WHERE stu.course_id = crs.id) AS avg_final
+                FROM hist_courses AS crs
+                ORDER BY crs.code, crs.term;
+            ")
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("History SQL Query Failure",e))?;
+        if results.is_empty() {
+            println!("Nothing archived yet, refresh archives the concluded courses");
+            return Ok(());
+        }
+        println!("{:20} {:8} {:4} {:4} {:9}", "CODE", "TERM", "STU", "ASGN", "AVG-FINAL");
+        println!("{:-<20} {:-<8} {:-<4} {:-<4} {:-<9}", "", "", "", "", "");
+        for result in results {
+            println!("{:20} {:8} {:4} {:4} {:>9}",
+                result.code, result.term, result.students, result.assignments,
+                result.avg_final
+                    .map(|x| format!("{:.2}%", x.round_dp(2)))
+                    .unwrap_or("-".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Compares assignments with the same name across the terms a course was
+    /// taught, including the current term.
+    async fn trends(&self, code : &str, filter : &str, zeros : ZeroMode) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            term : String,
+            current : bool,
+            name : String,
+            group_name : Option<String>,
+            percent : Option<Decimal>,
+            missing : bool,
+            excused : bool,
+        }
+        let results = sqlx::query_as::<_,Query>(
+                "
+                SELECT crs.term, FALSE AS current, asn.name, asn.group_name,
+                    sub.score / asn.points_possible * 100 AS percent,
+                    COALESCE(sub.missing, FALSE) AS missing,
+                    COALESCE(sub.excused, FALSE) AS excused
+                FROM hist_courses AS crs
+                INNER JOIN hist_assignments AS asn
+                    ON asn.course_id = crs.id
+                INNER JOIN hist_students AS stu
+                    ON stu.course_id = crs.id
+                LEFT JOIN hist_submissions AS sub
+                    ON sub.course_id = crs.id AND sub.assignment_id = asn.id AND sub.user_id = stu.id
+                WHERE UPPER(REPLACE(crs.code, ' ', '')) = $1
+                    AND asn.points_possible > 0
+                UNION ALL
+                SELECT crs.term, TRUE AS current, asn.name, grp.name AS group_name,
+                    sub.score / asn.points_possible * 100 AS percent,
+                    COALESCE(sub.missing, FALSE) AS missing,
+                    COALESCE(sub.excused, FALSE) AS excused
+                FROM curr_courses AS crs
+                INNER JOIN curr_assignments AS asn
+                    ON asn.course_id = crs.id
+                LEFT JOIN curr_assignment_groups AS grp
+                    ON grp.id = asn.assignment_group_id
+                INNER JOIN curr_students AS stu
+                    ON stu.course_id = crs.id
+                LEFT JOIN curr_submissions AS sub
+                    ON sub.assignment_id = asn.id AND sub.user_id = stu.id
+                WHERE UPPER(REPLACE(crs.code, ' ', '')) = $1
+                    AND asn.points_possible > 0
+                    AND NOT EXISTS (SELECT 1 FROM hist_courses AS hst WHERE hst.id = crs.id);
+            ")
+            .bind(HistoricalMod::normalize_code(code))
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("History SQL Query Failure",e))?;
+        if results.is_empty() {
+            println!("No assignments found for {}", code);
+            return Ok(());
+        }
+
+        let filter = filter.to_lowercase();
+        let mut assignments = BTreeMap::<String, (String, BTreeMap<(String, bool), TermResults>)>::new();
+        for result in results.iter() {
+            if !result.name.to_lowercase().contains(&filter) {
+                continue;
+            }
+            let (_, terms) = assignments.entry(result.name.clone())
+                .or_insert_with(|| (result.group_name.clone().unwrap_or_default(), BTreeMap::new()));
+            let term = terms.entry((result.term.clone(), result.current)).or_default();
+            if result.excused {
+                continue;
+            }
+            term.students += 1;
+            if result.missing {
+                term.missing += 1;
+            }
+            if let Some(percent) = result.percent {
+                if !(zeros.exclude && percent.is_zero()) {
+                    term.percents.push(percent);
+                }
+            }
+        }
+        if assignments.is_empty() {
+            println!("No assignments match '{}'", filter);
+            return Ok(());
+        }
+
+        println!("{}", zeros);
+        let band_header = BANDS.iter()
+            .map(|x| format!(" {:5}", x.0))
+            .collect::<String>();
+        let band_divider = BANDS.iter()
+            .map(|_| format!(" {:-<5}", ""))
+            .collect::<String>();
+        println!("{:10} {:4} {:4} {:7} {:7} {:7}{}",
+            "TERM", "STU", "GRD", "AVG-%", "MED-%", "MISS-%", band_header);
+        for (name, (group_name, terms)) in assignments.iter() {
+            println!("{:-<10} {:-<4} {:-<4} {:-<7} {:-<7} {:-<7}{}",
+                "", "", "", "", "", "", band_divider);
+            println!("{} ({})", name, group_name);
+            for ((term, current), results) in terms.iter() {
+                let mut bands = [0usize; BANDS.len()];
+                for percent in results.percents.iter() {
+                    let band = BANDS.iter()
+                        .position(|x| *percent >= Decimal::from(x.1))
+                        .unwrap_or(BANDS.len() - 1);
+                    bands[band] += 1;
+                }
+                let summary = Summary::new(results.percents.clone());
+                let missing = if results.students == 0 {
+                    Decimal::ZERO
+                }
+                else {
+                    Decimal::from(results.missing) / Decimal::from(results.students) * Decimal::ONE_HUNDRED
+                };
+                println!("{:10} {:4} {:4} {:>7} {:>7} {:6.2}%{}",
+                    format!("{}{}", term, if *current { " *" } else { "" }),
+                    results.students, results.percents.len(),
+                    summary.as_ref().map(|x| format!("{:.2}%", x.mean.round_dp(2))).unwrap_or("-".to_string()),
+                    summary.as_ref().map(|x| format!("{:.2}%", x.median.round_dp(2))).unwrap_or("-".to_string()),
+                    missing.round_dp(2),
+                    bands.iter().map(|x| format!(" {:5}", x)).collect::<String>());
+            }
+        }
+        Ok(())
+    }
+
+    /// Final grade distribution for each term a course was taught.  The
+    /// current term shows current grades, which are not final yet.
+    async fn grades(&self, code : &str) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            term : String,
+            current : bool,
+            grade : Option<String>,
+            score : Option<Decimal>,
+        }
+        let results = sqlx::query_as::<_,Query>(
+                "
+                SELECT crs.term, FALSE AS current, stu.final_grade AS grade, stu.final_score AS score
+                FROM hist_courses AS crs
+                INNER JOIN hist_students AS stu
+                    ON stu.course_id = crs.id
+                WHERE UPPER(REPLACE(crs.code, ' ', '')) = $1
+                UNION ALL
+                SELECT crs.term, TRUE AS current, stu.curr_grade AS grade, stu.curr_score AS score
+                FROM curr_courses AS crs
+                INNER JOIN curr_students AS stu
+                    ON stu.course_id = crs.id
+                WHERE UPPER(REPLACE(crs.code, ' ', '')) = $1
+                    AND NOT EXISTS (SELECT 1 FROM hist_courses AS hst WHERE hst.id = crs.id);
+            ")
+            .bind(HistoricalMod::normalize_code(code))
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("History SQL Query Failure",e))?;
+        if results.is_empty() {
+            println!("No students found for {}", code);
+            return Ok(());
+        }
+
+        let mut terms = BTreeMap::<(String, bool), (Vec<Decimal>, HashMap<String, i64>)>::new();
+        for result in results.iter() {
+            let (scores, letters) = terms.entry((result.term.clone(), result.current)).or_default();
+            if let Some(score) = result.score {
+                scores.push(score);
+            }
+            let letter = result.grade.clone().filter(|x| !x.is_empty()).unwrap_or("-".to_string());
+            *letters.entry(letter).or_default() += 1;
+        }
+        let mut columns = terms.values()
+            .flat_map(|x| x.1.keys().cloned())
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        columns.sort_by_key(|x| (LETTERS.iter().position(|y| y == x).unwrap_or(LETTERS.len()), x.clone()));
+        let widths = columns.iter()
+            .map(|x| x.chars().count().max(5))
+            .collect::<Vec<usize>>();
+
+        println!("{:10} {:4} {:7} {:7} {:7}{}",
+            "TERM", "STU", "AVG-%", "MED-%", "STD-DEV",
+            columns.iter().zip(widths.iter())
+                .map(|(x, w)| format!(" {:w$}", x, w = w))
+                .collect::<String>());
+        println!("{:-<10} {:-<4} {:-<7} {:-<7} {:-<7}{}", "", "", "", "", "",
+            widths.iter().map(|w| format!(" {:-<w$}", "", w = w)).collect::<String>());
+        for ((term, current), (scores, letters)) in terms.iter() {
+            let students = letters.values().sum::<i64>();
+            let summary = Summary::new(scores.clone());
+            // Letter counts as a share of the class, so terms of different
+            // sizes can be compared
+            let shares = columns.iter().zip(widths.iter())
+                .map(|(x, w)| {
+                    let count = letters.get(x).copied().unwrap_or(0);
+                    format!(" {:>w$}", format!("{:.0}%", Decimal::from(count * 100) / Decimal::from(students.max(1))), w = w)
+                })
+                .collect::<String>();
+            println!("{:10} {:4} {:>7} {:>7} {:>7}{}",
+                format!("{}{}", term, if *current { " *" } else { "" }),
+                students,
+                summary.as_ref().map(|x| format!("{:.2}%", x.mean.round_dp(2))).unwrap_or("-".to_string()),
+                summary.as_ref().map(|x| format!("{:.2}%", x.median.round_dp(2))).unwrap_or("-".to_string()),
+                summary.as_ref().map(|x| format!("{:.2}", x.std_dev.round_dp(2))).unwrap_or("-".to_string()),
+                shares);
+        }
+        Ok(())
+    }
+}
@@ -1,19 +1,10 @@
 pub mod module;
 pub mod current_mod;
+pub mod resubmit_mod;
+pub mod quiz_mod;
+pub mod attendance_mod;
+pub mod historical_mod;
+pub mod outcomes_mod;
+pub mod grade_engine;
+pub mod statistics;
 
-// double quiz
-    // improvement on 2nd submittal
-// resubmittals
-    // avg increase
-    // avg resubmittals
-    // relation to final grade
-// attendance bonus
-    // bonus
-    // relation to final grade
-// historical 
-    // assignment trends
-    // students and final grade
-// outcomes
-    // same as what university does
-
-// Include std dev & median grade for assignments
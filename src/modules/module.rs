@@ -6,16 +6,28 @@ use std::io::{BufReader, Read, BufWriter};
 use console::Term;
 use async_trait::async_trait;
 use chrono::prelude::Local;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use crate::macros::err;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ModuleType {
     Current,
+    Resubmit,
+    Quiz,
+    Attendance,
+    Historical,
+    Outcomes,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct ModuleInfo {
-    pub current_mod_refresh : Option<String>
+    pub current_mod_refresh : Option<String>,
+    pub resubmit_mod_refresh : Option<String>,
+    pub quiz_mod_refresh : Option<String>,
+    pub attendance_mod_refresh : Option<String>,
+    pub historical_mod_refresh : Option<String>,
+    pub outcomes_mod_refresh : Option<String>,
 }
 
 impl ModuleInfo {
@@ -25,7 +37,12 @@ impl ModuleInfo {
             Ok(module_info) => Ok(module_info),
             Err(_) => {
                 let module_info = ModuleInfo { 
-                    current_mod_refresh: None
+                    current_mod_refresh: None,
+                    resubmit_mod_refresh: None,
+                    quiz_mod_refresh: None,
+                    attendance_mod_refresh: None,
+                    historical_mod_refresh: None,
+                    outcomes_mod_refresh: None,
                 };
                 module_info.save_module_info()?;
                 Ok(module_info)
@@ -51,6 +68,11 @@ impl ModuleInfo {
         let dt = Local::now().format("%d/%m/%Y %H:%M").to_string();        
         match module {
             ModuleType::Current => self.current_mod_refresh = Some(dt),
+            ModuleType::Resubmit => self.resubmit_mod_refresh = Some(dt),
+            ModuleType::Quiz => self.quiz_mod_refresh = Some(dt),
+            ModuleType::Attendance => self.attendance_mod_refresh = Some(dt),
+            ModuleType::Historical => self.historical_mod_refresh = Some(dt),
+            ModuleType::Outcomes => self.outcomes_mod_refresh = Some(dt),
         };
         self.save_module_info()
     }
@@ -68,7 +90,12 @@ impl ModuleInfo {
 
     pub fn get_module_refresh(&self, module : &ModuleType) -> Option<String> {
         match module {
-            ModuleType::Current => self.current_mod_refresh.clone()
+            ModuleType::Current => self.current_mod_refresh.clone(),
+            ModuleType::Resubmit => self.resubmit_mod_refresh.clone(),
+            ModuleType::Quiz => self.quiz_mod_refresh.clone(),
+            ModuleType::Attendance => self.attendance_mod_refresh.clone(),
+            ModuleType::Historical => self.historical_mod_refresh.clone(),
+            ModuleType::Outcomes => self.outcomes_mod_refresh.clone(),
         }
     }
     
@@ -78,10 +105,50 @@ impl ModuleInfo {
 pub trait ModuleTrait {
     fn get_name(&self) -> String;
     async fn process_cmd(&mut self, cmd : Vec<&str>) -> Result<bool,String>;
-    async fn refresh(&mut self) -> Result<(),String>;
+    async fn refresh(&mut self, full : bool) -> Result<(),String>;
     fn help(&self);
 }
 
+/// Whether a report treats zero scores as a real attempt, and where that
+/// setting came from.
+#[derive(Clone, Copy)]
+pub struct ZeroMode {
+    pub exclude : bool,
+    overridden : bool,
+}
+
+impl ZeroMode {
+
+    /// The zero score mode for one command: `configured` unless the command
+    /// has --zeros or --no-zeros.  Returns None after reporting an unknown
+    /// option.
+    pub fn from_flags(configured : bool, flags : &[&str]) -> Option<Self> {
+        let mut mode = ZeroMode { exclude : configured, overridden : false };
+        for flag in flags {
+            match *flag {
+                "--zeros" => mode = ZeroMode { exclude : false, overridden : true },
+                "--no-zeros" => mode = ZeroMode { exclude : true, overridden : true },
+                _ => {
+                    println!("Unknown Option: {}", flag);
+                    return None;
+                }
+            }
+        }
+        Some(mode)
+    }
+}
+
+impl fmt::Display for ZeroMode {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Zero scores: {} ({})",
+            if self.exclude { "excluded" } else { "included" },
+            match (self.overridden, self.exclude) {
+                (false, _) => "config",
+                (true, true) => "--no-zeros",
+                (true, false) => "--zeros",
+            })
+    }
+}
 
 pub fn print_vec<T>(vec : &Vec<T>) where T : fmt::Display {
     for item in vec {
@@ -89,6 +156,107 @@ pub fn print_vec<T>(vec : &Vec<T>) where T : fmt::Display {
     }
 }
 
+/// Heading for an assignment group, e.g. "Quizzes (weight 20%, drop lowest 1)".
+pub fn group_header(name : &str, weight : Option<Decimal>, drop_lowest : i32, drop_highest : i32) -> String {
+    let mut details = Vec::<String>::new();
+    if let Some(weight) = weight {
+        details.push(format!("weight {}%", weight.round_dp(2).normalize()));
+    }
+    if drop_lowest > 0 {
+        details.push(format!("drop lowest {}", drop_lowest));
+    }
+    if drop_highest > 0 {
+        details.push(format!("drop highest {}", drop_highest));
+    }
+    if details.is_empty() {
+        name.to_string()
+    }
+    else {
+        format!("{} ({})", name, details.join(", "))
+    }
+}
+
+/// Finds students (or assignments) by Canvas id or name.  Names match
+/// ignoring case when every word of the query starts a word of the name, so
+/// "ave sor" finds "Avery Sorensen".  Failing that the names closest in
+/// spelling are returned, as long as they are within a typo or two of the
+/// query.  A number that is no candidate's id is looked up as a name.
+pub fn find_by_name(query : &str, candidates : &[(i32, String)]) -> Vec<(i32, String)> {
+    if let Ok(id) = query.trim().parse::<i32>() {
+        let by_id = candidates.iter()
+            .filter(|x| x.0 == id)
+            .cloned()
+            .collect::<Vec<(i32, String)>>();
+        if !by_id.is_empty() {
+            return by_id;
+        }
+    }
+    let query = query.trim().to_lowercase();
+    let exact = candidates.iter()
+        .filter(|x| x.1.to_lowercase() == query)
+        .cloned()
+        .collect::<Vec<(i32, String)>>();
+    if !exact.is_empty() {
+        return exact;
+    }
+    let words = query.split_whitespace().collect::<Vec<&str>>();
+    let prefixed = candidates.iter()
+        .filter(|x| {
+            let name = x.1.to_lowercase();
+            let name_words = name.split_whitespace().collect::<Vec<&str>>();
+            words.iter().all(|w| name_words.iter().any(|n| n.starts_with(w)))
+        })
+        .cloned()
+        .collect::<Vec<(i32, String)>>();
+    if !prefixed.is_empty() || words.is_empty() {
+        return prefixed;
+    }
+    let distances = candidates.iter()
+        .map(|x| edit_distance(&query, &x.1.to_lowercase()))
+        .collect::<Vec<usize>>();
+    let closest = distances.iter().copied().min().unwrap_or(usize::MAX);
+    if closest > (query.chars().count() / 4).max(2) {
+        return Vec::new();
+    }
+    candidates.iter().zip(distances)
+        .filter(|x| x.1 == closest)
+        .map(|x| x.0.clone())
+        .collect()
+}
+
+// Levenshtein distance, counted in characters
+fn edit_distance(a : &str, b : &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// One block per value scaled between the lowest and highest value, e.g.
+/// "▁▃▅█".  A flat series is drawn at half height.
+pub fn sparkline(values : &[Decimal]) -> String {
+    const BLOCKS : [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let low = values.iter().copied().min().unwrap_or_default();
+    let high = values.iter().copied().max().unwrap_or_default();
+    values.iter()
+        .map(|x| {
+            if high == low {
+                return BLOCKS[3];
+            }
+            let level = (*x - low) / (high - low) * Decimal::from(BLOCKS.len() - 1);
+            BLOCKS[level.round().to_usize().unwrap_or(0).min(BLOCKS.len() - 1)]
+        })
+        .collect()
+}
+
 pub fn print_progress_bar(mut curr : u32, total : u32) {
     if curr > total {
         curr = total;
@@ -116,3 +284,60 @@ pub fn print_progress_bar(mut curr : u32, total : u32) {
     }
     let _ = io::stdout().flush();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn students() -> Vec<(i32, String)> {
+        vec![
+            (5001, "Avery Sorensen".to_string()),
+            (5002, "Casey Delgado".to_string()),
+            (5003, "Casey Dalton".to_string()),
+            (5004, "Jordan 3000".to_string()),
+        ]
+    }
+
+    fn ids(found : Vec<(i32, String)>) -> Vec<i32> {
+        found.into_iter().map(|x| x.0).collect()
+    }
+
+    #[test]
+    fn exact_name_wins_over_prefixes() {
+        assert_eq!(ids(find_by_name("casey delgado", &students())), vec![5002]);
+        assert_eq!(ids(find_by_name(" 5003 ", &students())), vec![5003]);
+    }
+
+    #[test]
+    fn every_word_starts_a_name_word() {
+        assert_eq!(ids(find_by_name("ave sor", &students())), vec![5001]);
+        assert_eq!(ids(find_by_name("sor", &students())), vec![5001]);
+        assert_eq!(ids(find_by_name("casey", &students())), vec![5002, 5003]);
+        assert_eq!(ids(find_by_name("casey d", &students())), vec![5002, 5003]);
+    }
+
+    #[test]
+    fn number_that_is_not_an_id_is_a_name() {
+        assert_eq!(ids(find_by_name("3000", &students())), vec![5004]);
+    }
+
+    #[test]
+    fn typos_within_the_cutoff() {
+        // One substitution and one missing letter
+        assert_eq!(ids(find_by_name("avery sorenson", &students())), vec![5001]);
+        assert_eq!(ids(find_by_name("casey delgdo", &students())), vec![5002]);
+        // Too far from every name
+        assert!(find_by_name("morgan", &students()).is_empty());
+        assert!(find_by_name("avery sxrxnxxn", &students()).is_empty());
+    }
+
+    #[test]
+    fn edit_distance_counts_characters() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tokio::task::JoinHandle;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use crate::data::bulk::unique_rows;
+use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
+use crate::data::outcome::{Outcome, RubricLink};
+use crate::data::outcome_result::OutcomeResult;
+use crate::data::section::SectionStudent;
+use crate::modules::module::{print_vec, ModuleTrait};
+use crate::macros::err;
+
+// Column headings of the accreditation export
+const EXPORT_HEADER : [&str; 9] = [
+    "Course", "Term", "Section", "Outcome", "Description", "Mastery Threshold",
+    "Students Assessed", "Students Meeting Mastery", "Percent Meeting Mastery"
+];
+
+// Everything downloaded for one course during a refresh
+struct CourseData {
+    outcomes : Vec<Outcome>,
+    links : Vec<RubricLink>,
+    results : Vec<OutcomeResult>,
+    sections : Vec<SectionStudent>,
+}
+
+// Each enrolled student's rolled up score per outcome
+struct Rollups {
+    outcomes : Vec<Outcome>,
+    students : Vec<(i32, String)>,
+    scores : HashMap<(i32, i32), (Decimal, bool)>,
+    sections : BTreeMap<String, HashSet<i32>>,
+}
+
+// Students assessed on an outcome and how many of them mastered it
+#[derive(Default)]
+struct Mastery {
+    assessed : i64,
+    mastered : i64,
+    total : Decimal,
+}
+
+impl Mastery {
+    fn add(&mut self, score : Decimal, mastered : bool) {
+        self.assessed += 1;
+        self.total += score;
+        if mastered {
+            self.mastered += 1;
+        }
+    }
+
+    fn percent(&self) -> Option<Decimal> {
+        if self.assessed == 0 {
+            return None;
+        }
+        Some(Decimal::from(self.mastered) / Decimal::from(self.assessed) * Decimal::ONE_HUNDRED)
+    }
+
+    fn average(&self) -> Option<Decimal> {
+        if self.assessed == 0 {
+            return None;
+        }
+        Some(self.total / Decimal::from(self.assessed))
+    }
+}
+
+impl Rollups {
+    /// Mastery of an outcome among `students`, or the whole course.
+    fn mastery(&self, outcome_id : i32, students : Option<&HashSet<i32>>) -> Mastery {
+        let mut mastery = Mastery::default();
+        for (user_id, _) in self.students.iter() {
+            if students.is_some_and(|x| !x.contains(user_id)) {
+                continue;
+            }
+            if let Some((score, mastered)) = self.scores.get(&(outcome_id, *user_id)) {
+                mastery.add(*score, *mastered);
+            }
+        }
+        mastery
+    }
+}
+
+pub struct OutcomesMod {
+    config : Config,
+    course_lookup : HashMap<String, i32>,
+    database : Pool<Postgres>,
+    client : CanvasClient,
+}
+
+#[async_trait]
+impl ModuleTrait for OutcomesMod {
+    fn get_name(&self) -> String {
+        "Outcomes".to_string()
+    }
+
+    async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        if let Some(command) = parsed.first() {
+            return match *command {
+                "outcomes" | "sections" | "students" | "export" => {
+                    let Some(course) = parsed.get(1) else {
+                        println!("Missing Course ID");
+                        return Ok(true);
+                    };
+                    let Some(course_id) = self.course_lookup.get(*course).copied() else {
+                        println!("Invalid Course ID");
+                        return Ok(true);
+                    };
+                    match *command {
+                        "outcomes" => self.outcomes(course_id).await?,
+                        "sections" => self.sections(course_id).await?,
+                        "students" => self.students(course_id).await?,
+                        _ => match parsed.get(2) {
+                            Some(file) => self.export(course_id, Path::new(file)).await?,
+                            None => println!("Missing CSV File")
+                        }
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    /// Downloads outcomes, their rubric alignments, outcome results and
+    /// section membership for the configured courses and replaces the
+    /// stored data in one transaction.
+    async fn refresh(&mut self, _full : bool) -> Result<(),String> {
+        println!("Loading Module: {}", self.get_name());
+
+        let course_ids = self.course_lookup
+            .values()
+            .map(|x| x.to_owned())
+            .collect::<Vec<i32>>();
+
+        self.client.reset_stats();
+        let mut threads = Vec::<JoinHandle<Result<CourseData,String>>>::new();
+        for course_id in course_ids.iter() {
+            let c = self.client.clone();
+            let i = *course_id;
+            threads.push(tokio::spawn(async move {
+                Ok(CourseData {
+                    outcomes : Outcome::fetch(&c, i).await?,
+                    links : RubricLink::fetch(&c, i).await?,
+                    results : OutcomeResult::fetch(&c, i).await?,
+                    sections : SectionStudent::fetch(&c, i).await?,
+                })
+            }));
+        }
+        let mut outcomes = Vec::<Outcome>::new();
+        let mut links = Vec::<RubricLink>::new();
+        let mut results = Vec::<OutcomeResult>::new();
+        let mut sections = Vec::<SectionStudent>::new();
+        for t in threads {
+            let mut data = t.await
+                .map_err(|e| err!("Refresh Task Failure",e))??;
+            outcomes.append(&mut data.outcomes);
+            links.append(&mut data.links);
+            results.append(&mut data.results);
+            sections.append(&mut data.sections);
+        }
+        println!("{}", self.client.stats());
+        // An outcome can be linked into more than one outcome group
+        let outcomes = unique_rows(outcomes, |x| (x.course_id, x.id));
+        let links = unique_rows(links, |x| (x.course_id, x.rubric_id, x.criterion_id.clone()));
+        let results = unique_rows(results, |x| x.id);
+        let sections = unique_rows(sections, |x| (x.course_id, x.id, x.user_id));
+
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        let stats = vec![
+            Outcome::store(&mut tx, &outcomes).await?,
+            RubricLink::store(&mut tx, &links).await?,
+            OutcomeResult::store(&mut tx, &results).await?,
+            SectionStudent::store(&mut tx, &sections).await?,
+        ];
+        Outcome::retain(&mut tx, &course_ids, &outcomes).await?;
+        RubricLink::retain(&mut tx, &course_ids, &links).await?;
+        OutcomeResult::retain(&mut tx, &course_ids, &results).await?;
+        SectionStudent::retain(&mut tx, &course_ids, &sections).await?;
+        tx.commit()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        print_vec(&stats);
+
+        Ok(())
+    }
+
+    fn help(&self) {
+        println!("outcomes <course id>");
+        println!("sections <course id>");
+        println!("students <course id>");
+        println!("export <course id> <csv file>");
+        print!("     <course id> =");
+        for course in &self.config.current_config.courses {
+            print!(" {}", course.0);
+        }
+        println!();
+        println!("     students come from the Current module");
+    }
+
+}
+
+impl OutcomesMod {
+    pub fn new(config : Config, database : Pool<Postgres>, client : CanvasClient) -> Self {
+        let mut course_lookup = HashMap::<String,i32>::new();
+        for course in config.current_config.courses.iter() {
+            course_lookup.insert(course.0.clone(), course.1);
+        }
+        Self { config, course_lookup, database, client }
+    }
+
+    async fn rollups(&self, course : i32) -> Result<Rollups, String> {
+        let outcomes = Outcome::load(&self.database, course).await?;
+        let students = sqlx::query_as::<_,(i32, String)>(
+                "
+                SELECT id, name
+                FROM curr_students
+                WHERE course_id = $1
+                ORDER BY name, id;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))?;
+        let results = sqlx::query_as::<_,(i32, i32, Option<DateTime<Utc>>, Decimal)>(
+                "
+                SELECT outcome_id, user_id, assessed_at, score
+                FROM out_results
+                WHERE course_id = $1 AND score IS NOT NULL;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))?;
+        let members = sqlx::query_as::<_,(String, i32)>(
+                "
+                SELECT name, user_id
+                FROM curr_sections
+                WHERE course_id = $1;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))?;
+
+        let mut by_student = HashMap::<(i32, i32), Vec<(Option<DateTime<Utc>>, Decimal)>>::new();
+        for (outcome_id, user_id, assessed_at, score) in results {
+            by_student.entry((outcome_id, user_id)).or_default().push((assessed_at, score));
+        }
+        let mut scores = HashMap::<(i32, i32), (Decimal, bool)>::new();
+        for outcome in outcomes.iter() {
+            for (user_id, _) in students.iter() {
+                if let Some(rollup) = by_student.get(&(outcome.id, *user_id))
+                    .and_then(|x| outcome.rollup(x)) {
+                    scores.insert((outcome.id, *user_id), rollup);
+                }
+            }
+        }
+        let mut sections = BTreeMap::<String, HashSet<i32>>::new();
+        for (name, user_id) in members {
+            sections.entry(name).or_default().insert(user_id);
+        }
+        Ok(Rollups { outcomes, students, scores, sections })
+    }
+
+    async fn outcomes(&self, course : i32) -> Result<(), String> {
+        let rollups = self.rollups(course).await?;
+        if rollups.outcomes.is_empty() {
+            println!("No outcomes linked in this course");
+            return Ok(());
+        }
+        let links = sqlx::query_as::<_,(i32, String, Vec<i32>)>(
+                "
+                SELECT outcome_id, rubric_title, assignment_ids
+                FROM out_rubric_links
+                WHERE course_id = $1;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))?;
+
+        println!("{:40} {:16} {:7} {:4} {:4} {:4} {:4} {:9} {:7}",
+            "OUTCOME", "METHOD", "MASTERY", "RUBR", "ASGN", "ASSD", "MAST", "MASTERY-%", "AVG-SCR");
+        println!("{:-<40} {:-<16} {:-<7} {:-<4} {:-<4} {:-<4} {:-<4} {:-<9} {:-<7}",
+            "", "", "", "", "", "", "", "", "");
+        for outcome in rollups.outcomes.iter() {
+            let outcome_links = links.iter()
+                .filter(|x| x.0 == outcome.id)
+                .collect::<Vec<_>>();
+            let rubrics = outcome_links.iter()
+                .map(|x| x.1.clone())
+                .collect::<HashSet<String>>();
+            let assignments = outcome_links.iter()
+                .flat_map(|x| x.2.iter().copied())
+                .collect::<HashSet<i32>>();
+            let mastery = rollups.mastery(outcome.id, None);
+            println!("{:40} {:16} {:>7} {:4} {:4} {:4} {:4} {:>9} {:>7}",
+                outcome.title.chars().take(40).collect::<String>(),
+                OutcomesMod::method(outcome),
+                format!("{}/{}",
+                    outcome.mastery_points.unwrap_or_default().normalize(),
+                    outcome.points_possible.unwrap_or_default().normalize()),
+                rubrics.len(), assignments.len(),
+                mastery.assessed, mastery.mastered,
+                mastery.percent().map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()),
+                mastery.average().map(|x| format!("{:.2}", x.round_dp(2))).unwrap_or("-".to_string()));
+        }
+        println!();
+        println!("A student masters an outcome when their rolled up score reaches the");
+        println!("mastery points (n_mastery: when enough results reach it)");
+        Ok(())
+    }
+
+    async fn sections(&self, course : i32) -> Result<(), String> {
+        let rollups = self.rollups(course).await?;
+        if rollups.outcomes.is_empty() {
+            println!("No outcomes linked in this course");
+            return Ok(());
+        }
+        let mut groups = vec![("All Sections".to_string(), None)];
+        groups.extend(rollups.sections.iter().map(|(name, students)| (name.clone(), Some(students))));
+
+        println!("{:40} {:4} {:4} {:9} {:7}", "OUTCOME", "ASSD", "MAST", "MASTERY-%", "AVG-SCR");
+        for (name, students) in groups {
+            println!("{:-<40} {:-<4} {:-<4} {:-<9} {:-<7}", "", "", "", "", "");
+            println!("{} ({} students)", name,
+                students.map(|x| x.len()).unwrap_or(rollups.students.len()));
+            for outcome in rollups.outcomes.iter() {
+                let mastery = rollups.mastery(outcome.id, students);
+                println!("{:40} {:4} {:4} {:>9} {:>7}",
+                    outcome.title.chars().take(40).collect::<String>(),
+                    mastery.assessed, mastery.mastered,
+                    mastery.percent().map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()),
+                    mastery.average().map(|x| format!("{:.2}", x.round_dp(2))).unwrap_or("-".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn students(&self, course : i32) -> Result<(), String> {
+        let rollups = self.rollups(course).await?;
+        if rollups.outcomes.is_empty() {
+            println!("No outcomes linked in this course");
+            return Ok(());
+        }
+        // Outcome titles are too long for columns, so they are numbered
+        for (index, outcome) in rollups.outcomes.iter().enumerate() {
+            println!("O{} = {}", index + 1, outcome.title);
+        }
+        println!();
+        let outcome_header = (1..=rollups.outcomes.len())
+            .map(|x| format!(" {:6}", format!("O{}", x)))
+            .collect::<String>();
+        let outcome_divider = rollups.outcomes.iter()
+            .map(|_| format!(" {:-<6}", ""))
+            .collect::<String>();
+        println!("{:40} {:4} {:4} {:9}{}", "NAME", "ASSD", "MAST", "MASTERY-%", outcome_header);
+        println!("{:-<40} {:-<4} {:-<4} {:-<9}{}", "", "", "", "", outcome_divider);
+        for (user_id, name) in rollups.students.iter() {
+            let mut mastery = Mastery::default();
+            let mut cells = String::new();
+            for outcome in rollups.outcomes.iter() {
+                match rollups.scores.get(&(outcome.id, *user_id)) {
+                    Some((score, mastered)) => {
+                        mastery.add(*score, *mastered);
+                        cells += &format!(" {:>6}", format!("{:.2}{}", score.round_dp(2), if *mastered { "*" } else { " " }));
+                    }
+                    None => cells += &format!(" {:>6}", "- ")
+                }
+            }
+            println!("{:40} {:4} {:4} {:>9}{}",
+                name.chars().take(40).collect::<String>(),
+                mastery.assessed, mastery.mastered,
+                mastery.percent().map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()),
+                cells);
+        }
+        println!();
+        println!("* = mastered");
+        Ok(())
+    }
+
+    /// Writes mastery per outcome for the course and each section in the
+    /// layout of the accreditation reports: one row per outcome and section.
+    async fn export(&self, course : i32, path : &Path) -> Result<(), String> {
+        let rollups = self.rollups(course).await?;
+        let (code, term) = sqlx::query_as::<_,(String, String)>(
+                "
+                SELECT code, term FROM curr_courses WHERE id = $1;
+            ")
+            .bind(course)
+            .fetch_optional(&self.database)
+            .await
+            .map_err(|e| err!("Outcome SQL Query Failure",e))?
+            .unwrap_or_default();
+
+        let mut writer = csv::Writer::from_path(path)
+            .map_err(|e| err!(format!("CSV File Failure\n{}", path.display()), e))?;
+        let mut rows = 0;
+        let mut write = |row : Vec<String>| writer.write_record(&row)
+            .map_err(|e| err!(format!("CSV Write Failure\n{}", path.display()), e));
+        write(EXPORT_HEADER.iter().map(|x| x.to_string()).collect())?;
+        for outcome in rollups.outcomes.iter() {
+            let mut groups = vec![("All Sections".to_string(), None)];
+            groups.extend(rollups.sections.iter().map(|(name, students)| (name.clone(), Some(students))));
+            for (section, students) in groups {
+                let mastery = rollups.mastery(outcome.id, students);
+                write(vec![
+                    code.clone(),
+                    term.clone(),
+                    section,
+                    outcome.title.clone(),
+                    outcome.description.clone().unwrap_or_default(),
+                    outcome.mastery_points.unwrap_or_default().normalize().to_string(),
+                    mastery.assessed.to_string(),
+                    mastery.mastered.to_string(),
+                    mastery.percent().map(|x| x.round_dp(1).to_string()).unwrap_or_default(),
+                ])?;
+                rows += 1;
+            }
+        }
+        writer.flush()
+            .map_err(|e| err!(format!("CSV Write Failure\n{}", path.display()), e))?;
+        println!("Wrote {} rows to {}", rows, path.display());
+        Ok(())
+    }
+
+    fn method(outcome : &Outcome) -> String {
+        let method = outcome.calculation_method.clone().unwrap_or("decaying_average".to_string());
+        match outcome.calculation_int {
+            Some(n) if method == "decaying_average" || method == "n_mastery" => format!("{} {}", method, n),
+            _ => method
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::task::JoinHandle;
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use crate::data::assignment::Assignment;
+use crate::data::bulk::unique_rows;
+use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
+use crate::data::quiz_attempt::QuizAttempt;
+use crate::modules::module::{group_header, print_vec, ModuleTrait, ZeroMode};
+use crate::modules::statistics::{average, percent};
+use crate::macros::err;
+
+// One student's attempts at one quiz, as percentages of the points possible
+struct QuizTaker {
+    user_id : i32,
+    attempts : usize,
+    first : Decimal,
+    best : Decimal,
+    last : Decimal,
+}
+
+pub struct QuizMod {
+    config : Config,
+    course_lookup : HashMap<String, i32>,
+    database : Pool<Postgres>,
+    client : CanvasClient,
+}
+
+#[async_trait]
+impl ModuleTrait for QuizMod {
+    fn get_name(&self) -> String {
+        "Quiz".to_string()
+    }
+
+    async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        let (flags, parsed) : (Vec<&str>, Vec<&str>) = parsed.into_iter()
+            .partition(|x| x.starts_with("--"));
+        if let Some(command) = parsed.first() {
+            if *command != "quizzes" {
+                return Ok(false);
+            }
+            let Some(zeros) = ZeroMode::from_flags(self.config.current_config.exclude_zero_grades, &flags) else {
+                return Ok(true);
+            };
+            return match *command {
+                "quizzes" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.quizzes(*course_id, zeros).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    /// Downloads every attempt of every quiz in the configured courses and
+    /// replaces the stored attempts in one transaction.
+    async fn refresh(&mut self, _full : bool) -> Result<(),String> {
+        println!("Loading Module: {}", self.get_name());
+
+        let course_ids = self.course_lookup
+            .values()
+            .map(|x| x.to_owned())
+            .collect::<Vec<i32>>();
+
+        self.client.reset_stats();
+        let mut threads = Vec::<JoinHandle<Result<Vec<QuizAttempt>,String>>>::new();
+        for course_id in course_ids.iter() {
+            let c = self.client.clone();
+            let i = *course_id;
+            threads.push(tokio::spawn(async move {
+                let mut attempts = Vec::<QuizAttempt>::new();
+                for assignment in Assignment::fetch(&c, i).await? {
+                    if let Some(quiz_id) = assignment.quiz_id {
+                        attempts.append(&mut QuizAttempt::fetch(&c, i, quiz_id, assignment.id,
+                            assignment.points_possible).await?);
+                    }
+                }
+                Ok(attempts)
+            }));
+        }
+        let mut attempts = Vec::<QuizAttempt>::new();
+        for t in threads {
+            attempts.append(&mut t.await
+                .map_err(|e| err!("Refresh Task Failure",e))??);
+        }
+        println!("{}", self.client.stats());
+        let attempts = unique_rows(attempts, |x| (x.quiz_id, x.user_id, x.attempt));
+
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        let stats = vec![QuizAttempt::store(&mut tx, &attempts).await?];
+        QuizAttempt::retain(&mut tx, &course_ids, &attempts).await?;
+        tx.commit()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        print_vec(&stats);
+
+        Ok(())
+    }
+
+    fn help(&self) {
+        println!("quizzes <course id> [--zeros|--no-zeros]");
+        print!("     <course id> =");
+        for course in &self.config.current_config.courses {
+            print!(" {}", course.0);
+        }
+        println!();
+        println!("     names, groups and current scores come from the Current module");
+    }
+
+}
+
+impl QuizMod {
+    pub fn new(config : Config, database : Pool<Postgres>, client : CanvasClient) -> Self {
+        let mut course_lookup = HashMap::<String,i32>::new();
+        for course in config.current_config.courses.iter() {
+            course_lookup.insert(course.0.clone(), course.1);
+        }
+        Self { config, course_lookup, database, client }
+    }
+
+    /// Each quiz's takers.  With zeros excluded a zero score is not a real
+    /// attempt and is left out.
+    async fn takers(&self, course : i32, zeros : ZeroMode) -> Result<HashMap<i32, Vec<QuizTaker>>, String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            assignment_id : i32,
+            user_id : i32,
+            percent : Decimal,
+        }
+        let results = sqlx::query_as::<_,Query>(
+                "
+                SELECT assignment_id, user_id, score / points_possible * 100 AS percent
+                FROM quiz_attempts
+                WHERE course_id = $1 and points_possible > 0 and score IS NOT NULL
+                    and NOT ($2 AND score = 0)
+                ORDER BY assignment_id, user_id, attempt;
+            ")
+            .bind(course)
+            .bind(zeros.exclude)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Quiz SQL Query Failure",e))?;
+
+        let mut attempts = BTreeMap::<(i32, i32), Vec<Decimal>>::new();
+        for result in results {
+            attempts.entry((result.assignment_id, result.user_id)).or_default().push(result.percent);
+        }
+        let mut takers = HashMap::<i32, Vec<QuizTaker>>::new();
+        for ((assignment_id, user_id), percents) in attempts {
+            takers.entry(assignment_id).or_default().push(QuizTaker {
+                user_id,
+                attempts : percents.len(),
+                first : percents[0],
+                best : percents.iter().copied().max().unwrap_or_default(),
+                last : percents[percents.len() - 1],
+            });
+        }
+        Ok(takers)
+    }
+
+    /// Compares first, best and last attempts of each quiz, and the current
+    /// course score of students who retook it against those who did not.
+    async fn quizzes(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            id : i32,
+            name : String,
+            group : i32,
+            group_name : Option<String>,
+            group_weight : Option<Decimal>,
+            drop_lowest : Option<i32>,
+            drop_highest : Option<i32>,
+            weighted : bool,
+        }
+        let quizzes = sqlx::query_as::<_,Query>(
+                "
+                SELECT asn.id, asn.name, asn.assignment_group_id AS group,
+                    grp.name AS group_name, grp.group_weight,
+                    grp.drop_lowest, grp.drop_highest, crs.weighted
+                FROM curr_assignments AS asn
+                INNER JOIN curr_courses AS crs
+                    ON crs.id = asn.course_id
+                LEFT JOIN curr_assignment_groups AS grp
+                    ON grp.id = asn.assignment_group_id
+                WHERE asn.course_id = $1 and asn.quiz_id IS NOT NULL
+                ORDER BY grp.position, asn.assignment_group_id, asn.name;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Quiz SQL Query Failure",e))?;
+        // Students without a score yet are left out of the averages
+        let scores = sqlx::query_as::<_,(i32, Decimal)>(
+                "
+                SELECT id, curr_score FROM curr_students
+                WHERE course_id = $1 and curr_score IS NOT NULL;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Quiz SQL Query Failure",e))?
+            .into_iter()
+            .collect::<HashMap<i32, Decimal>>();
+        let takers = self.takers(course, zeros).await?;
+
+        println!("{}", zeros);
+        println!("{:40} {:5} {:6} {:5} {:6} {:6} {:6} {:6} {:6} {:8} {:8}",
+            "QUIZ", "TAKEN", "RETAKE", "RTK%", "FIRST%", "BEST%", "LAST%",
+            "IMPR-B", "IMPR-L", "RTK-SCR%", "ONE-SCR%");
+        let mut retakers = HashSet::<i32>::new();
+        let mut takers_any = HashSet::<i32>::new();
+        let mut prev_group = -1;
+        for quiz in quizzes {
+            if prev_group != quiz.group {
+                println!("{:-<40} {:-<5} {:-<6} {:-<5} {:-<6} {:-<6} {:-<6} {:-<6} {:-<6} {:-<8} {:-<8}",
+                    "", "", "", "", "", "", "", "", "", "", "");
+                println!("{}", group_header(
+                    quiz.group_name.as_deref().unwrap_or("Unknown Group"),
+                    if quiz.weighted { quiz.group_weight } else { None },
+                    quiz.drop_lowest.unwrap_or(0),
+                    quiz.drop_highest.unwrap_or(0)));
+                prev_group = quiz.group;
+            }
+            let quiz_takers = takers.get(&quiz.id).map(|x| x.as_slice()).unwrap_or(&[]);
+            let retook = quiz_takers.iter().filter(|x| x.attempts > 1).collect::<Vec<&QuizTaker>>();
+            let once = quiz_takers.iter().filter(|x| x.attempts == 1).collect::<Vec<&QuizTaker>>();
+            takers_any.extend(quiz_takers.iter().map(|x| x.user_id));
+            retakers.extend(retook.iter().map(|x| x.user_id));
+            let course_score = |list : &[&QuizTaker]| average(list.iter()
+                .filter_map(|x| scores.get(&x.user_id).copied())
+                .collect());
+            println!("{:40} {:5} {:6} {:4.1}% {:5.1}% {:5.1}% {:5.1}% {:6.1} {:6.1} {:7.2}% {:7.2}%",
+                quiz.name.chars().take(40).collect::<String>(),
+                quiz_takers.len(), retook.len(),
+                percent(retook.len(), quiz_takers.len()),
+                average(quiz_takers.iter().map(|x| x.first).collect()),
+                average(quiz_takers.iter().map(|x| x.best).collect()),
+                average(quiz_takers.iter().map(|x| x.last).collect()),
+                average(retook.iter().map(|x| x.best - x.first).collect()),
+                average(retook.iter().map(|x| x.last - x.first).collect()),
+                course_score(&retook), course_score(&once));
+        }
+
+        let never = takers_any.difference(&retakers).copied().collect::<Vec<i32>>();
+        let course_score = |ids : &mut dyn Iterator<Item = &i32>| average(ids
+            .filter_map(|x| scores.get(x).copied())
+            .collect());
+        println!();
+        println!("Current course score of students who retook any quiz: {:6.2}% ({} students)",
+            course_score(&mut retakers.iter()), retakers.len());
+        println!("Current course score of students who never retook:    {:6.2}% ({} students)",
+            course_score(&mut never.iter()), never.len());
+        Ok(())
+    }
+}
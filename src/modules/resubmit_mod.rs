@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, HashMap};
+use tokio::task::JoinHandle;
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use rust_decimal::Decimal;
+use crate::data::attempt::Attempt;
+use crate::data::bulk::unique_rows;
+use crate::data::config::Config;
+use crate::data::connections::CanvasClient;
+use crate::modules::module::{print_vec, ModuleTrait, ZeroMode};
+use crate::modules::statistics::{average, correlation, percent};
+use crate::macros::err;
+
+// Resubmission results for one student's submission to one assignment
+struct SubmissionSummary {
+    assignment_id : i32,
+    user_id : i32,
+    attempts : i32,
+    // Average change in score between graded attempts, as a percent of the
+    // points possible.  None without at least two graded attempts.
+    gain : Option<Decimal>,
+}
+
+const COMMANDS : [&str; 2] = ["assignments", "students"];
+
+pub struct ResubmitMod {
+    config : Config,
+    course_lookup : HashMap<String, i32>,
+    database : Pool<Postgres>,
+    client : CanvasClient,
+}
+
+#[async_trait]
+impl ModuleTrait for ResubmitMod {
+    fn get_name(&self) -> String {
+        "Resubmit".to_string()
+    }
+
+    async fn process_cmd(&mut self, parsed : Vec<&str>) -> Result<bool,String> {
+        let (flags, parsed) : (Vec<&str>, Vec<&str>) = parsed.into_iter()
+            .partition(|x| x.starts_with("--"));
+        if let Some(command) = parsed.first() {
+            if !COMMANDS.contains(command) {
+                return Ok(false);
+            }
+            let Some(zeros) = ZeroMode::from_flags(self.config.current_config.exclude_zero_grades, &flags) else {
+                return Ok(true);
+            };
+            return match *command {
+                "assignments" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.assignments(*course_id, zeros).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                "students" => {
+                    if let Some(course) = parsed.get(1) {
+                        if let Some(course_id) = self.course_lookup.get(*course) {
+                            self.students(*course_id, zeros).await?;
+                        }
+                        else {
+                            println!("Invalid Course ID");
+                        }
+                    }
+                    else {
+                        println!("Missing Course ID");
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    /// Downloads the submission history of every configured course and
+    /// replaces the stored attempts in one transaction.  Submission history
+    /// cannot be requested incrementally, so this is always a full refresh.
+    async fn refresh(&mut self, _full : bool) -> Result<(),String> {
+        println!("Loading Module: {}", self.get_name());
+
+        let course_ids = self.course_lookup
+            .values()
+            .map(|x| x.to_owned())
+            .collect::<Vec<i32>>();
+
+        self.client.reset_stats();
+        let mut threads = Vec::<JoinHandle<Result<Vec<Attempt>,String>>>::new();
+        for course_id in course_ids.iter() {
+            let c = self.client.clone();
+            let i = *course_id;
+            threads.push(tokio::spawn(async move { Attempt::fetch(&c, i).await }));
+        }
+        let mut attempts = Vec::<Attempt>::new();
+        for t in threads {
+            attempts.append(&mut t.await
+                .map_err(|e| err!("Refresh Task Failure",e))??);
+        }
+        println!("{}", self.client.stats());
+        let attempts = unique_rows(attempts, |x| (x.submission_id, x.attempt));
+
+        let mut tx = self.database.begin()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        let stats = vec![Attempt::store(&mut tx, &attempts).await?];
+        Attempt::retain(&mut tx, &course_ids, &attempts).await?;
+        tx.commit()
+            .await
+            .map_err(|e| err!("Refresh Transaction Failure",e))?;
+        print_vec(&stats);
+
+        Ok(())
+    }
+
+    fn help(&self) {
+        println!("assignments <course id> [--zeros|--no-zeros]");
+        println!("students <course id> [--zeros|--no-zeros]");
+        print!("     <course id> =");
+        for course in &self.config.current_config.courses {
+            print!(" {}", course.0);
+        }
+        println!();
+        println!("     names, points and current scores come from the Current module");
+    }
+
+}
+
+impl ResubmitMod {
+    pub fn new(config : Config, database : Pool<Postgres>, client : CanvasClient) -> Self {
+        let mut course_lookup = HashMap::<String,i32>::new();
+        for course in config.current_config.courses.iter() {
+            course_lookup.insert(course.0.clone(), course.1);
+        }
+        Self { config, course_lookup, database, client }
+    }
+
+    /// One summary per submission.  With zeros excluded a zero score is not
+    /// a graded attempt, so it does not count towards the gain.
+    async fn summaries(&self, course : i32, zeros : ZeroMode) -> Result<Vec<SubmissionSummary>, String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            submission_id : i32,
+            assignment_id : i32,
+            user_id : i32,
+            attempt : i32,
+            score : Option<Decimal>,
+            points_possible : Decimal,
+        }
+        let results = sqlx::query_as::<_,Query>(
+                "
+                SELECT att.submission_id, att.assignment_id, att.user_id,
+                    att.attempt, att.score, asn.points_possible
+                FROM resub_attempts AS att
+                INNER JOIN curr_assignments AS asn
+                    ON asn.id = att.assignment_id
+                WHERE att.course_id = $1 and asn.points_possible > 0
+                ORDER BY att.submission_id, att.attempt;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Resubmission SQL Query Failure",e))?;
+
+        let mut submissions = BTreeMap::<i32, Vec<Query>>::new();
+        for result in results {
+            submissions.entry(result.submission_id).or_default().push(result);
+        }
+        Ok(submissions.into_values()
+            .map(|attempts| {
+                let graded = attempts.iter()
+                    .filter_map(|x| x.score.map(|score| (x.attempt, score / x.points_possible * Decimal::ONE_HUNDRED)))
+                    .filter(|x| !(zeros.exclude && x.1.is_zero()))
+                    .collect::<Vec<(i32, Decimal)>>();
+                let gain = match (graded.first(), graded.last()) {
+                    (Some(first), Some(last)) if last.0 > first.0 =>
+                        Some((last.1 - first.1) / Decimal::from(last.0 - first.0)),
+                    _ => None
+                };
+                SubmissionSummary {
+                    assignment_id : attempts[0].assignment_id,
+                    user_id : attempts[0].user_id,
+                    attempts : attempts.iter().map(|x| x.attempt).max().unwrap_or(0),
+                    gain,
+                }
+            })
+            .collect())
+    }
+
+    async fn assignments(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            id : i32,
+            name : String,
+        }
+        let assignments = sqlx::query_as::<_,Query>(
+                "
+                SELECT asn.id, asn.name
+                FROM curr_assignments AS asn
+                LEFT JOIN curr_assignment_groups AS grp
+                    ON grp.id = asn.assignment_group_id
+                WHERE asn.course_id = $1 and asn.points_possible > 0
+                ORDER BY grp.position, asn.assignment_group_id, asn.name;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Resubmission SQL Query Failure",e))?;
+        let summaries = self.summaries(course, zeros).await?;
+
+        println!("{}", zeros);
+        println!("{:40} {:4} {:5} {:6} {:7} {:9}",
+            "ASSIGNMENT", "SUBM", "RESUB", "RESUB%", "AVG-ATT", "AVG-GAIN%");
+        println!("{:-<40} {:-<4} {:-<5} {:-<6} {:-<7} {:-<9}",
+            "", "", "", "", "", "");
+        for assignment in assignments {
+            let submitted = summaries.iter()
+                .filter(|x| x.assignment_id == assignment.id)
+                .collect::<Vec<&SubmissionSummary>>();
+            let resubmitted = submitted.iter()
+                .filter(|x| x.attempts > 1)
+                .collect::<Vec<_>>();
+            let gains = submitted.iter()
+                .filter_map(|x| x.gain)
+                .collect::<Vec<Decimal>>();
+            println!("{:40} {:4} {:5} {:5.1}% {:7.2} {:8.2}%",
+                assignment.name.chars().take(40).collect::<String>(),
+                submitted.len(), resubmitted.len(),
+                percent(resubmitted.len(), submitted.len()),
+                average(resubmitted.iter().map(|x| Decimal::from(x.attempts - 1)).collect()),
+                average(gains));
+        }
+        Ok(())
+    }
+
+    async fn students(&self, course : i32, zeros : ZeroMode) -> Result<(), String> {
+        #[derive(sqlx::FromRow)]
+        struct Query {
+            id : i32,
+            name : String,
+            curr_score : Option<Decimal>,
+        }
+        let students = sqlx::query_as::<_,Query>(
+                "
+                SELECT id, name, curr_score
+                FROM curr_students
+                WHERE course_id = $1
+                ORDER BY curr_score DESC NULLS LAST, name ASC;
+            ")
+            .bind(course)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| err!("Resubmission SQL Query Failure",e))?;
+        let summaries = self.summaries(course, zeros).await?;
+
+        println!("{}", zeros);
+        println!("{:40} {:4} {:5} {:5} {:9} {:7}",
+            "NAME", "SUBM", "RESUB", "EXTRA", "AVG-GAIN%", "SCORE-%");
+        println!("{:-<40} {:-<4} {:-<5} {:-<5} {:-<9} {:-<7}",
+            "", "", "", "", "", "");
+        let mut by_extra = Vec::<(Decimal, Decimal)>::new();
+        let mut by_gain = Vec::<(Decimal, Decimal)>::new();
+        for student in students {
+            let submitted = summaries.iter()
+                .filter(|x| x.user_id == student.id)
+                .collect::<Vec<&SubmissionSummary>>();
+            let resubmitted = submitted.iter().filter(|x| x.attempts > 1).count();
+            let extra = submitted.iter().map(|x| x.attempts - 1).sum::<i32>();
+            let gains = submitted.iter()
+                .filter_map(|x| x.gain)
+                .collect::<Vec<Decimal>>();
+            let has_gain = !gains.is_empty();
+            let gain = average(gains);
+            // Students without a score yet are left out of the correlations
+            if let Some(score) = student.curr_score {
+                by_extra.push((Decimal::from(extra), score));
+                if has_gain {
+                    by_gain.push((gain, score));
+                }
+            }
+            println!("{:40} {:4} {:5} {:5} {:8.2}% {:>7}",
+                student.name.chars().take(40).collect::<String>(),
+                submitted.len(), resubmitted, extra, gain,
+                student.curr_score.map(|x| format!("{:.2}%", x.round_dp(2))).unwrap_or("-".to_string()));
+        }
+
+        println!();
+        println!("Correlation with current score (-1 to 1):");
+        println!("    extra attempts      {}", ResubmitMod::format_correlation(correlation(&by_extra)));
+        println!("    average gain        {}", ResubmitMod::format_correlation(correlation(&by_gain)));
+        Ok(())
+    }
+
+    fn format_correlation(value : Option<Decimal>) -> String {
+        match value {
+            Some(value) => format!("{:5.2}", value.round_dp(2)),
+            None => "n/a".to_string()
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+
+/// Spread of a set of percentages.  The standard deviation is the population
+/// one since a report covers the whole class, and quartiles interpolate
+/// between neighbouring values like a spreadsheet's QUARTILE.
+pub struct Summary {
+    pub count : usize,
+    pub min : Decimal,
+    pub q1 : Decimal,
+    pub median : Decimal,
+    pub q3 : Decimal,
+    pub max : Decimal,
+    pub mean : Decimal,
+    pub std_dev : Decimal,
+}
+
+impl Summary {
+    pub fn new(mut values : Vec<Decimal>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort();
+        let count = values.len();
+        let mean = values.iter().sum::<Decimal>() / Decimal::from(count);
+        let variance = values.iter()
+            .map(|x| (x - mean) * (x - mean))
+            .sum::<Decimal>() / Decimal::from(count);
+        let std_dev = variance.to_f64()
+            .and_then(|x| Decimal::from_f64(x.sqrt()))
+            .unwrap_or_default();
+        Some(Self {
+            count,
+            min : values[0],
+            q1 : Summary::quantile(&values, Decimal::new(25, 2)),
+            median : Summary::quantile(&values, Decimal::new(50, 2)),
+            q3 : Summary::quantile(&values, Decimal::new(75, 2)),
+            max : values[count - 1],
+            mean,
+            std_dev,
+        })
+    }
+
+    fn quantile(sorted : &[Decimal], q : Decimal) -> Decimal {
+        let position = q * Decimal::from(sorted.len() - 1);
+        let index = position.floor().to_usize().unwrap_or(0);
+        let fraction = position - position.floor();
+        match sorted.get(index + 1) {
+            Some(next) => sorted[index] + (next - sorted[index]) * fraction,
+            None => sorted[index]
+        }
+    }
+}
+
+/// Pearson correlation of the pairs, None when either side does not vary.
+pub fn correlation(pairs : &[(Decimal, Decimal)]) -> Option<Decimal> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let count = Decimal::from(pairs.len());
+    let mean_x = pairs.iter().map(|x| x.0).sum::<Decimal>() / count;
+    let mean_y = pairs.iter().map(|x| x.1).sum::<Decimal>() / count;
+    let covariance = pairs.iter().map(|x| (x.0 - mean_x) * (x.1 - mean_y)).sum::<Decimal>();
+    let spread_x = pairs.iter().map(|x| (x.0 - mean_x) * (x.0 - mean_x)).sum::<Decimal>();
+    let spread_y = pairs.iter().map(|x| (x.1 - mean_y) * (x.1 - mean_y)).sum::<Decimal>();
+    if spread_x.is_zero() || spread_y.is_zero() {
+        return None;
+    }
+    let denominator = (spread_x.to_f64()? * spread_y.to_f64()?).sqrt();
+    Decimal::from_f64(covariance.to_f64()? / denominator)
+}
+
+/// Mean of the values to two places, zero for an empty list.
+pub fn average(values : Vec<Decimal>) -> Decimal {
+    if values.is_empty() {
+        return Decimal::ZERO;
+    }
+    (values.iter().sum::<Decimal>() / Decimal::from(values.len())).round_dp(2)
+}
+
+/// `part` as a percentage of `total` to one place, zero when there is none.
+pub fn percent(part : usize, total : usize) -> Decimal {
+    if total == 0 {
+        return Decimal::ZERO;
+    }
+    (Decimal::from(part) / Decimal::from(total) * Decimal::ONE_HUNDRED).round_dp(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimals(x : &[i64]) -> Vec<Decimal> {
+        x.iter().map(|y| Decimal::from(*y)).collect()
+    }
+
+    #[test]
+    fn quartiles_interpolate_between_values() {
+        let summary = Summary::new(decimals(&[4, 1, 3, 2])).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.min, Decimal::from(1));
+        assert_eq!(summary.q1, Decimal::new(175, 2));
+        assert_eq!(summary.median, Decimal::new(25, 1));
+        assert_eq!(summary.q3, Decimal::new(325, 2));
+        assert_eq!(summary.max, Decimal::from(4));
+        assert_eq!(summary.mean, Decimal::new(25, 1));
+    }
+
+    #[test]
+    fn std_dev_is_the_population_one() {
+        // The sample standard deviation of these would be about 2.14
+        let summary = Summary::new(decimals(&[2, 4, 4, 4, 5, 5, 7, 9])).unwrap();
+        assert_eq!(summary.mean, Decimal::from(5));
+        assert_eq!(summary.std_dev, Decimal::from(2));
+    }
+
+    #[test]
+    fn single_and_empty_sets() {
+        let summary = Summary::new(decimals(&[7])).unwrap();
+        assert_eq!((summary.min, summary.q1, summary.median, summary.q3, summary.max),
+            (Decimal::from(7), Decimal::from(7), Decimal::from(7), Decimal::from(7), Decimal::from(7)));
+        assert_eq!(summary.std_dev, Decimal::ZERO);
+        assert!(Summary::new(vec![]).is_none());
+    }
+
+    #[test]
+    fn correlation_of_perfectly_related_pairs() {
+        let pairs = [(1, 2), (2, 4), (3, 6)].iter()
+            .map(|x| (Decimal::from(x.0), Decimal::from(x.1)))
+            .collect::<Vec<(Decimal, Decimal)>>();
+        assert_eq!(correlation(&pairs), Some(Decimal::ONE));
+        let opposite = pairs.iter().map(|x| (x.0, -x.1)).collect::<Vec<(Decimal, Decimal)>>();
+        assert_eq!(correlation(&opposite), Some(-Decimal::ONE));
+    }
+
+    #[test]
+    fn correlation_needs_two_varying_pairs() {
+        assert_eq!(correlation(&[]), None);
+        assert_eq!(correlation(&[(Decimal::ONE, Decimal::TWO)]), None);
+        // Every x is the same, so there is no spread to correlate
+        let flat = [(5, 1), (5, 2), (5, 3)].iter()
+            .map(|x| (Decimal::from(x.0), Decimal::from(x.1)))
+            .collect::<Vec<(Decimal, Decimal)>>();
+        assert_eq!(correlation(&flat), None);
+    }
+
+    #[test]
+    fn average_and_percent_of_nothing_are_zero() {
+        assert_eq!(average(vec![]), Decimal::ZERO);
+        assert_eq!(average(decimals(&[1, 2, 2])), Decimal::new(167, 2));
+        assert_eq!(percent(0, 0), Decimal::ZERO);
+        assert_eq!(percent(1, 3), Decimal::new(333, 1));
+    }
+}
@@ -40,8 +40,14 @@ impl Shell {
                 if let Some(command) = parsed.first() {
                     match *command {
                         "refresh" => {
-                            module.refresh().await?;
-                            self.module_info.update_refresh(&self.selected)?;
+                            let full = parsed.contains(&"--full");
+                            match module.refresh(full).await {
+                                Ok(()) => self.module_info.update_refresh(&self.selected)?,
+                                Err(e) => {
+                                    println!("{}",e);
+                                    println!("Refresh failed, keeping the previous data");
+                                }
+                            }
                         }
                         "help" => {
                             module.help();
@@ -64,11 +70,31 @@ impl Shell {
 
     fn module_cmd(&self, parsed : Vec<&str>) -> Option<ModuleType> {
         if let Some(module) = parsed.get(1) {
-            match *module {
+            return match *module {
                 "current" => {
                     println!("Switched to Module Current");
                     Some(ModuleType::Current)
                 }
+                "resubmit" => {
+                    println!("Switched to Module Resubmit");
+                    Some(ModuleType::Resubmit)
+                }
+                "quiz" => {
+                    println!("Switched to Module Quiz");
+                    Some(ModuleType::Quiz)
+                }
+                "attendance" => {
+                    println!("Switched to Module Attendance");
+                    Some(ModuleType::Attendance)
+                }
+                "historical" => {
+                    println!("Switched to Module Historical");
+                    Some(ModuleType::Historical)
+                }
+                "outcomes" => {
+                    println!("Switched to Module Outcomes");
+                    Some(ModuleType::Outcomes)
+                }
                 _ => { println!("Invalid Module"); None }
             };
         }
@@ -83,7 +109,9 @@ impl Shell {
     }
 
     fn help() {
-        println!("refresh : reload data for current module");
+        println!("refresh [--full] : reload data for current module");
+        println!("     --full = reload everything instead of only what changed");
+        println!("module [current|resubmit|quiz|attendance|historical|outcomes] : switch module, or list modules and last refresh");
         println!("help : show module specific and general command");
         println!("exit : close the program")
     }